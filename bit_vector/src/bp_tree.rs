@@ -0,0 +1,444 @@
+// Implement succinct ordinal tree navigation over a balanced parentheses bit-vector (1 = open, 0 = close)
+// nodes are identified by the position of their open parenthesis, the root is at 0
+// excess(i) = opens - closes in [0, i], searched with a range min tree over blocks of excess minima
+// (each min tree node stores its minimum relative to the excess just before its first block, so it fits in a few bits)
+// methods: find_close/find_open: matching parenthesis
+            // enclose/parent: open parenthesis of the enclosing pair
+            // first_child/next_sibling: navigate children
+            // subtree_size/depth: number of nodes under v (v included) and distance to the root
+
+use bitvec::prelude::*;
+use crate::{FromBits, SpaceUsage, BitAccess, Rank};
+use serde::{Serialize,Deserialize};
+
+use crate::select::SelectSupport;
+use crate::int_vector::IntVector;
+
+pub const BLOCK_SIZE: usize = 512;
+
+// for each byte of parentheses (first parenthesis in the lowest bit): the lowest excess reached after one of
+// its bits, the last bit reaching it, and the excess of the whole byte
+const BYTE_MIN_EXCESS: [(i8, u8, i8); 256] = byte_min_excess_table();
+
+const fn byte_min_excess_table() -> [(i8, u8, i8); 256] {
+    let mut table: [(i8, u8, i8); 256] = [(0, 0, 0); 256];
+    let mut byte: usize = 0;
+    while byte < 256 {
+        let (mut excess, mut min, mut last): (i8, i8, u8) = (0, i8::MAX, 0);
+        let mut bit: usize = 0;
+        while bit < 8 {
+            excess += if (byte >> bit) & 1 == 1 { 1 } else { -1 };
+            if excess <= min {
+                min = excess;
+                last = bit as u8;
+            }
+            bit += 1;
+        }
+        table[byte] = (min, last, excess);
+        byte += 1;
+    }
+    table
+}
+
+#[derive(Serialize,Deserialize, Debug)]
+pub struct BpTree {
+    bits: SelectSupport,
+    min_tree: Vec<IntVector> // level k node j: 1 - (min excess in blocks [j<<k, (j+1)<<k) - excess before them), leaves first
+}
+
+impl BpTree {
+    pub fn from_parens(parens: &str) -> BpTree {
+        // build from a string of '(' and ')'
+        let bit_v: BitVec = parens.chars().map(|c| match c {
+            '(' => true,
+            ')' => false,
+            _ => panic!("invalid parenthesis {}", c)
+        }).collect();
+        FromBits::from_bits(bit_v)
+    }
+
+    pub fn from_parent_array(parents: &[Option<usize>]) -> (BpTree, Vec<usize>) {
+        // build from parent of each node (None for the root), children are visited in increasing node order
+        // also returns the open parenthesis position of each node
+        let mut children: Vec<Vec<usize>> = vec![Vec::new(); parents.len()];
+        let mut root: Option<usize> = None;
+        for (node, parent) in parents.iter().enumerate() {
+            match parent {
+                Some(p) => children[*p].push(node),
+                None if root.is_none() => root = Some(node),
+                None => panic!("tree has more than one root")
+            }
+        }
+        let root: usize = root.expect("tree has no root");
+        let mut bit_v: BitVec = BitVec::with_capacity(2*parents.len());
+        let mut positions: Vec<usize> = vec![0; parents.len()];
+        // iterative dfs: (node, index of the next child to visit)
+        let mut stack: Vec<(usize, usize)> = vec![(root, 0)];
+        positions[root] = 0;
+        bit_v.push(true);
+        while let Some((node, next)) = stack.pop() {
+            if next < children[node].len() {
+                let child: usize = children[node][next];
+                stack.push((node, next+1));
+                stack.push((child, 0));
+                positions[child] = bit_v.len();
+                bit_v.push(true);
+            } else {
+                bit_v.push(false);
+            }
+        }
+        if bit_v.len() != 2*parents.len() {
+            panic!("parent array is not a single tree");
+        }
+        (FromBits::from_bits(bit_v), positions)
+    }
+
+    fn create_min_tree(bit_v: &BitVec) -> Vec<IntVector> {
+        // minimum excess inside each block, then each level pairs up the nodes below it until one is left
+        // a node's minimum is at most 1 above the excess before it and at most its length below, so level k
+        // needs log2(BLOCK_SIZE)+1+k bits per node
+        let num_blocks: usize = bit_v.len().div_ceil(BLOCK_SIZE).max(1);
+        let mut mins: Vec<isize> = vec![isize::MAX; num_blocks];
+        let mut starts: Vec<isize> = vec![0; num_blocks]; // excess before each block
+        let mut excess: isize = 0;
+        for (i, bit) in bit_v.iter().enumerate() {
+            if i.is_multiple_of(BLOCK_SIZE) {
+                starts[i/BLOCK_SIZE] = excess;
+            }
+            excess += if *bit { 1 } else { -1 };
+            mins[i/BLOCK_SIZE] = mins[i/BLOCK_SIZE].min(excess);
+        }
+        let mut min_tree: Vec<IntVector> = Vec::new();
+        loop {
+            let width: usize = IntVector::width_for(1 + (BLOCK_SIZE << min_tree.len()));
+            let mut level: IntVector = IntVector::with_capacity(width, mins.len());
+            for (&min, &start) in mins.iter().zip(&starts) {
+                // an empty bit-vector has no minimum, store the largest one a node can have
+                level.push((1 - min.min(start+1) + start) as usize);
+            }
+            min_tree.push(level);
+            if mins.len() == 1 {
+                return min_tree;
+            }
+            mins = mins.chunks(2).map(|pair| pair.iter().cloned().min().unwrap()).collect();
+            starts = starts.iter().step_by(2).cloned().collect();
+        }
+    }
+
+    fn node_min_excess(&self, level: usize, node: usize) -> isize {
+        // minimum excess in the blocks under node of min_tree level
+        let start: usize = (node << level)*BLOCK_SIZE;
+        2*self.bits.rank1(start) as isize - start as isize + 1 - self.min_tree[level].get(node) as isize
+    }
+
+    pub fn len(&self) -> usize {
+        // length of the parentheses sequence
+        self.bits.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.len()/2
+    }
+
+    pub fn as_bitvec(&self) -> &BitVec {
+        // the parentheses themselves
+        self.bits.as_bitvec()
+    }
+
+    pub fn is_open(&self, i: usize) -> bool {
+        self.bits.get(i)
+    }
+
+    pub fn excess(&self, i: usize) -> isize {
+        // opens - closes in positions [0, i]
+        2*self.bits.rank1(i+1) as isize - (i+1) as isize
+    }
+
+    fn step(&self, i: usize) -> isize {
+        if self.is_open(i) { 1 } else { -1 }
+    }
+
+    pub fn num_blocks(&self) -> usize {
+        self.len().div_ceil(BLOCK_SIZE)
+    }
+
+    pub fn block_min_excess(&self, block: usize) -> isize {
+        // minimum excess reached in positions of block
+        self.node_min_excess(0, block)
+    }
+
+    pub fn scan_min_excess(&self, a: usize, b: usize) -> (usize, isize) {
+        // rightmost position of the minimum excess in [a, b] and that excess (meant for within a block)
+        // whole aligned bytes are looked up in BYTE_MIN_EXCESS, the bits around them are stepped one by one
+        let bit_v: &BitVec = self.as_bitvec();
+        let mut excess: isize = self.excess(a);
+        let mut best: (usize, isize) = (a, excess);
+        let mut j: usize = a+1;
+        while j <= b {
+            if j.is_multiple_of(8) && j+8 <= b+1 {
+                let (min, last, total): (i8, u8, i8) = BYTE_MIN_EXCESS[bit_v[j..j+8].load_le::<u8>() as usize];
+                if excess+min as isize <= best.1 {
+                    best = (j+last as usize, excess+min as isize);
+                }
+                excess += total as isize;
+                j += 8;
+            } else {
+                excess += self.step(j);
+                if excess <= best.1 {
+                    best = (j, excess);
+                }
+                j += 1;
+            }
+        }
+        best
+    }
+
+    fn fwd_search(&self, i: usize, target: isize) -> Option<usize> {
+        // first j > i with excess(j) <= target (target below excess(i), so excess(j) = target)
+        let n: usize = self.len();
+        let block: usize = i/BLOCK_SIZE;
+        let mut excess: isize = self.excess(i);
+        for j in i+1..((block+1)*BLOCK_SIZE).min(n) {
+            excess += self.step(j);
+            if excess <= target {
+                return Some(j);
+            }
+        }
+        // first block after i's block whose minimum reaches target
+        let found: usize = self.first_block_at_or_after(self.min_tree.len()-1, 0, block+1, target)?;
+        let start: usize = found*BLOCK_SIZE;
+        let mut excess: isize = self.excess(start) - self.step(start);
+        for j in start..((found+1)*BLOCK_SIZE).min(n) {
+            excess += self.step(j);
+            if excess <= target {
+                return Some(j);
+            }
+        }
+        None
+    }
+
+    fn bwd_search(&self, i: usize, target: isize) -> Option<isize> {
+        // last j < i with excess(j) <= target, -1 if only the (virtual, excess 0) start qualifies
+        let block: usize = i/BLOCK_SIZE;
+        let mut excess: isize = self.excess(i);
+        for j in (block*BLOCK_SIZE..i).rev() {
+            excess -= self.step(j+1);
+            if excess <= target {
+                return Some(j as isize);
+            }
+        }
+        if block > 0 {
+            if let Some(found) = self.last_block_before(self.min_tree.len()-1, 0, block, target) {
+                let end: usize = (found+1)*BLOCK_SIZE-1;
+                let mut excess: isize = self.excess(end);
+                for j in (found*BLOCK_SIZE..=end).rev() {
+                    if excess <= target {
+                        return Some(j as isize);
+                    }
+                    excess -= self.step(j);
+                }
+            }
+        }
+        if target >= 0 {
+            Some(-1)
+        } else {
+            None
+        }
+    }
+
+    fn first_block_at_or_after(&self, level: usize, node: usize, from: usize, target: isize) -> Option<usize> {
+        // leftmost block >= from under node of min_tree level whose minimum is <= target
+        if node >= self.min_tree[level].len() || (node+1) << level <= from || self.node_min_excess(level, node) > target {
+            return None;
+        }
+        if level == 0 {
+            return Some(node);
+        }
+        self.first_block_at_or_after(level-1, 2*node, from, target)
+            .or_else(|| self.first_block_at_or_after(level-1, 2*node+1, from, target))
+    }
+
+    fn last_block_before(&self, level: usize, node: usize, before: usize, target: isize) -> Option<usize> {
+        // rightmost block < before under node of min_tree level whose minimum is <= target
+        if node >= self.min_tree[level].len() || node << level >= before || self.node_min_excess(level, node) > target {
+            return None;
+        }
+        if level == 0 {
+            return Some(node);
+        }
+        self.last_block_before(level-1, 2*node+1, before, target)
+            .or_else(|| self.last_block_before(level-1, 2*node, before, target))
+    }
+
+    pub fn find_close(&self, i: usize) -> usize {
+        // matching close parenthesis of the open parenthesis at i
+        if !self.is_open(i) {
+            panic!("find_close on a close parenthesis");
+        }
+        self.fwd_search(i, self.excess(i)-1).unwrap()
+    }
+
+    pub fn find_open(&self, i: usize) -> usize {
+        // matching open parenthesis of the close parenthesis at i
+        if self.is_open(i) {
+            panic!("find_open on an open parenthesis");
+        }
+        (self.bwd_search(i, self.excess(i)).unwrap()+1) as usize
+    }
+
+    pub fn enclose(&self, i: usize) -> Option<usize> {
+        // open parenthesis of the closest pair enclosing the open parenthesis at i
+        self.bwd_search(i, self.excess(i)-2).map(|j| (j+1) as usize)
+    }
+
+    pub fn parent(&self, v: usize) -> Option<usize> {
+        self.enclose(v)
+    }
+
+    pub fn first_child(&self, v: usize) -> Option<usize> {
+        if v+1 < self.len() && self.is_open(v+1) {
+            Some(v+1)
+        } else {
+            None
+        }
+    }
+
+    pub fn next_sibling(&self, v: usize) -> Option<usize> {
+        let next: usize = self.find_close(v)+1;
+        if next < self.len() && self.is_open(next) {
+            Some(next)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_leaf(&self, v: usize) -> bool {
+        self.first_child(v).is_none()
+    }
+
+    pub fn subtree_size(&self, v: usize) -> usize {
+        // number of nodes in the subtree rooted at v (including v)
+        (self.find_close(v)-v).div_ceil(2)
+    }
+
+    pub fn depth(&self, v: usize) -> usize {
+        // number of edges from the root to v
+        (self.excess(v)-1) as usize
+    }
+
+    pub fn preorder(&self, v: usize) -> usize {
+        // preorder number of node v (root is 0)
+        self.bits.rank1(v)
+    }
+
+    pub fn node(&self, k: usize) -> usize {
+        // node with preorder number k
+        self.bits.select1(k+1)-1
+    }
+}
+
+impl FromBits for BpTree {
+    fn from_bits(bit_v: BitVec) -> BpTree {
+        let min_tree: Vec<IntVector> = BpTree::create_min_tree(&bit_v);
+        // the root's minimum is relative to excess 0
+        if bit_v.count_ones()*2 != bit_v.len() || min_tree[min_tree.len()-1].get(0) > 1 {
+            panic!("parentheses are not balanced");
+        }
+        BpTree {bits: SelectSupport::from_bits(bit_v), min_tree}
+    }
+}
+
+impl SpaceUsage for BpTree {
+    fn payload(&self) -> usize {
+        self.len()
+    }
+
+    fn overhead(&self) -> usize {
+        // rank structure plus min tree in bits (the parentheses themselves are 2 bits per node)
+        self.bits.overhead() + self.min_tree.iter().map(|level| level.size()).sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    fn naive_close(parens: &[bool], i: usize) -> usize {
+        let mut excess: isize = 0;
+        (i..parens.len()).find(|&j| {
+            excess += if parens[j] { 1 } else { -1 };
+            excess == 0
+        }).unwrap()
+    }
+
+    fn naive_open(parens: &[bool], i: usize) -> usize {
+        let mut excess: isize = 0;
+        (0..=i).rev().find(|&j| {
+            excess += if parens[j] { -1 } else { 1 };
+            excess == 0
+        }).unwrap()
+    }
+
+
+    fn check_tree(parents: &[Option<usize>]) {
+        let (bp, positions): (BpTree, Vec<usize>) = BpTree::from_parent_array(parents);
+        let parens: Vec<bool> = bp.as_bitvec().iter().map(|bit| *bit).collect();
+        let closes: Vec<usize> = (0..parens.len()).map(|i| if parens[i] { naive_close(&parens, i) } else { 0 }).collect();
+        // open parentheses before i whose pair ends after i, innermost first
+        let (parens, closes): (&[bool], &[usize]) = (&parens, &closes);
+        let enclosing = |i: usize| (0..i).rev().filter(move |&j| parens[j] && closes[j] > i);
+        for i in 0..parens.len() {
+            if parens[i] {
+                let close: usize = closes[i];
+                assert_eq!(bp.find_close(i), close, "find_close({})", i);
+                assert_eq!(bp.enclose(i), enclosing(i).next(), "enclose({})", i);
+                let next: Option<usize> = Some(close+1).filter(|&j| j < parens.len() && parens[j]);
+                assert_eq!(bp.next_sibling(i), next, "next_sibling({})", i);
+                assert_eq!(bp.subtree_size(i), parens[i..close].iter().filter(|&&bit| bit).count());
+                assert_eq!(bp.depth(i), enclosing(i).count(), "depth({})", i);
+            } else {
+                assert_eq!(bp.find_open(i), naive_open(parens, i), "find_open({})", i);
+            }
+        }
+        for (node, parent) in parents.iter().enumerate() {
+            assert_eq!(bp.parent(positions[node]), parent.map(|p| positions[p]));
+        }
+    }
+
+    #[test]
+    fn matches_naive_tree() {
+        let mut rng: StdRng = StdRng::seed_from_u64(30);
+        // sizes cross the 512 bit blocks, parents near the node make deep trees, any earlier node bushy ones
+        for (n, reach) in [(1, 1), (2, 1), (256, 3), (257, 1000), (700, 2), (1500, 1500), (3000, 5)] {
+            let parents: Vec<Option<usize>> = (0..n).map(|node| match node {
+                0 => None,
+                _ => Some(node - rng.gen_range(1..=reach.min(node)))
+            }).collect();
+            check_tree(&parents);
+        }
+    }
+
+    #[test]
+    fn path_and_star() {
+        // a path closes everything in the last blocks, a star never goes deeper than 2
+        let path: Vec<Option<usize>> = (0..1200usize).map(|node| node.checked_sub(1)).collect();
+        let star: Vec<Option<usize>> = (0..1200).map(|node| if node == 0 { None } else { Some(0) }).collect();
+        check_tree(&path);
+        check_tree(&star);
+    }
+
+    #[test]
+    fn min_tree_is_compact() {
+        let parents: Vec<Option<usize>> = (0..100_000usize).map(|node| node.checked_sub(1)).collect();
+        let bp: BpTree = BpTree::from_parent_array(&parents).0;
+        assert_eq!(bp.min_tree[0].width(), BLOCK_SIZE.ilog2() as usize + 1);
+        let min_tree_bits: usize = bp.min_tree.iter().map(|level| level.size()).sum();
+        assert!(min_tree_bits*16 < bp.len(), "{} bits for {} parentheses", min_tree_bits, bp.len());
+        assert_eq!(bp.block_min_excess(bp.num_blocks()-1), 0);
+    }
+}
@@ -0,0 +1,92 @@
+// Implement directly addressable codes (DACs) for variable length integer values
+// each value is split into chunk_width-bit chunks, level l holds the lth chunk of every value that has one,
+// and a bit-vector per level marks which values continue so rank1 finds their chunk in the next level
+// methods: get: value at index i
+            // size: size of DACs in bits (same accounting as SparseArray::size)
+            // save/load: save/load DACs to/from file (Persist)
+
+use bitvec::prelude::*;
+use crate::{FromBits, SpaceUsage, BitAccess};
+use serde::{Serialize,Deserialize};
+
+use crate::int_vector::IntVector;
+use crate::rank::RankSupport;
+
+#[derive(Serialize,Deserialize, Debug)]
+pub struct DacVector {
+    levels: Vec<IntVector>, // chunks of the values still going at each level, least significant first
+    continues: Vec<RankSupport>, // whether each value at a level has another chunk (none for the last level)
+    chunk_width: usize,
+    len: usize
+}
+
+impl DacVector {
+    pub fn new(values: &[usize], chunk_width: usize) -> DacVector {
+        if chunk_width == 0 || chunk_width > usize::BITS as usize {
+            panic!("chunk width must be between 1 and {} bits", usize::BITS);
+        }
+        let mut levels: Vec<IntVector> = Vec::new();
+        let mut continues: Vec<RankSupport> = Vec::new();
+        let mut current: Vec<usize> = values.to_vec();
+        let mask: usize = if chunk_width == usize::BITS as usize { usize::MAX } else { (1 << chunk_width) - 1 };
+        loop {
+            let mut chunks: IntVector = IntVector::with_capacity(chunk_width, current.len());
+            let mut more: BitVec = BitVec::with_capacity(current.len());
+            let mut next: Vec<usize> = Vec::new();
+            for &value in current.iter() {
+                chunks.push(value & mask);
+                let rest: usize = value.checked_shr(chunk_width as u32).unwrap_or(0);
+                more.push(rest != 0);
+                if rest != 0 {
+                    next.push(rest);
+                }
+            }
+            levels.push(chunks);
+            if next.is_empty() {
+                break;
+            }
+            continues.push(FromBits::from_bits(more));
+            current = next;
+        }
+        DacVector {levels, continues, chunk_width, len: values.len()}
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn num_levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn get(&self, i: usize) -> usize {
+        // value at index i, following continuation bits down the levels
+        if i >= self.len {
+            panic!("invalid position entry");
+        }
+        let mut value: usize = 0;
+        let mut pos: usize = i;
+        for (level, chunks) in self.levels.iter().enumerate() {
+            value |= chunks.get(pos) << (level*self.chunk_width);
+            match self.continues.get(level) {
+                Some(more) if more.get(pos) => pos = more.rank1(pos),
+                _ => break
+            }
+        }
+        value
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.len).map(|i| self.get(i))
+    }
+
+    pub fn size(&self) -> usize {
+        // size of DACs in bits (chunks, continuation bit-vectors and their rank structures)
+        self.levels.iter().map(|chunks| chunks.size()).sum::<usize>()
+            + self.continues.iter().map(|more| more.len() + more.overhead()).sum::<usize>()
+    }
+}
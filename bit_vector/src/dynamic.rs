@@ -0,0 +1,508 @@
+// Implement a dynamic bit-vector: a treap of leaf blocks, each node keeps its block and the bits/ones/blocks in its subtree
+// blocks are split when they pass 2*BLOCK_BITS bits and dropped when empty, so every operation walks one O(log n) path
+// methods: insert: insert a bit before position i
+            // remove: remove the bit at position i
+            // set/get: overwrite or read the bit at position i
+            // rank1: number of 1s before position i (exclusive)
+            // select1/select0: first position j for which rank1(j) = i (rank0(j) = i), same as SelectSupport
+            // from_rank_support/to_rank_support: convert from/to the static RankSupport
+            // overhead: size of tree nodes in bits
+            // save/load: save/load dynamic bit-vector to/from file
+
+use bitvec::prelude::*;
+use crate::{FromBits, SpaceUsage, BitAccess, Rank, Select};
+use serde::{Serialize,Deserialize};
+
+use crate::rank::RankSupport;
+
+// bits per block when building, blocks split in half when they grow past twice this
+const BLOCK_BITS: usize = 512;
+// words of bookkeeping per node (children, priority, subtree bits/ones/blocks)
+const NODE_WORDS: usize = 6;
+
+#[derive(Serialize,Deserialize, Debug)]
+struct Node {
+    bits: BitVec,
+    priority: u64,
+    left: Option<usize>,
+    right: Option<usize>,
+    len: usize, // bits in subtree
+    ones: usize, // 1s in subtree
+    blocks: usize // nodes in subtree
+}
+
+#[derive(Serialize,Deserialize, Debug)]
+pub struct DynamicBitVector {
+    nodes: Vec<Node>,
+    free: Vec<usize>, // slots of removed nodes, reused before growing nodes
+    root: Option<usize>,
+    seed: u64 // xorshift state for priorities
+}
+
+impl DynamicBitVector {
+    pub fn from_rank_support(rank_support: &RankSupport) -> DynamicBitVector {
+        FromBits::from_bits(rank_support.as_bitvec().clone())
+    }
+
+    pub fn to_rank_support(&self) -> RankSupport {
+        FromBits::from_bits(self.to_bitvec())
+    }
+
+    pub fn to_bitvec(&self) -> BitVec {
+        // concatenate blocks in order (in-order walk)
+        let mut bit_v: BitVec = BitVec::with_capacity(self.len());
+        let mut stack: Vec<usize> = Vec::new();
+        let mut t: Option<usize> = self.root;
+        while t.is_some() || !stack.is_empty() {
+            while let Some(node) = t {
+                stack.push(node);
+                t = self.nodes[node].left;
+            }
+            let node: usize = stack.pop().unwrap();
+            bit_v.extend_from_bitslice(&self.nodes[node].bits);
+            t = self.nodes[node].right;
+        }
+        bit_v
+    }
+
+    pub fn len(&self) -> usize {
+        self.subtree_len(self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.subtree_ones(self.root)
+    }
+
+    fn subtree_len(&self, t: Option<usize>) -> usize {
+        t.map_or(0, |node| self.nodes[node].len)
+    }
+
+    fn subtree_ones(&self, t: Option<usize>) -> usize {
+        t.map_or(0, |node| self.nodes[node].ones)
+    }
+
+    fn subtree_blocks(&self, t: Option<usize>) -> usize {
+        t.map_or(0, |node| self.nodes[node].blocks)
+    }
+
+    fn update(&mut self, node: usize) {
+        // recompute subtree totals from the children
+        let (left, right): (Option<usize>, Option<usize>) = (self.nodes[node].left, self.nodes[node].right);
+        let len: usize = self.subtree_len(left) + self.nodes[node].bits.len() + self.subtree_len(right);
+        let ones: usize = self.subtree_ones(left) + self.nodes[node].bits.count_ones() + self.subtree_ones(right);
+        let blocks: usize = self.subtree_blocks(left) + 1 + self.subtree_blocks(right);
+        let n: &mut Node = &mut self.nodes[node];
+        n.len = len;
+        n.ones = ones;
+        n.blocks = blocks;
+    }
+
+    fn next_priority(&mut self) -> u64 {
+        // xorshift64, deterministic so saved trees rebuild the same way
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        self.seed
+    }
+
+    fn new_node(&mut self, bits: BitVec) -> usize {
+        let priority: u64 = self.next_priority();
+        let node: Node = Node {bits, priority, left: None, right: None, len: 0, ones: 0, blocks: 0};
+        let slot: usize = match self.free.pop() {
+            Some(slot) => {
+                self.nodes[slot] = node;
+                slot
+            },
+            None => {
+                self.nodes.push(node);
+                self.nodes.len()-1
+            }
+        };
+        self.update(slot);
+        slot
+    }
+
+    fn merge(&mut self, a: Option<usize>, b: Option<usize>) -> Option<usize> {
+        // concatenate two treaps, higher priority becomes the root
+        match (a, b) {
+            (None, _) => b,
+            (_, None) => a,
+            (Some(x), Some(y)) => {
+                if self.nodes[x].priority > self.nodes[y].priority {
+                    let right: Option<usize> = self.merge(self.nodes[x].right, b);
+                    self.nodes[x].right = right;
+                    self.update(x);
+                    a
+                } else {
+                    let left: Option<usize> = self.merge(a, self.nodes[y].left);
+                    self.nodes[y].left = left;
+                    self.update(y);
+                    b
+                }
+            }
+        }
+    }
+
+    fn split(&mut self, t: Option<usize>, k: usize) -> (Option<usize>, Option<usize>) {
+        // split a treap into its first k blocks and the rest
+        let node: usize = match t {
+            Some(node) => node,
+            None => return (None, None)
+        };
+        let left_blocks: usize = self.subtree_blocks(self.nodes[node].left);
+        if k <= left_blocks {
+            let (a, b): (Option<usize>, Option<usize>) = self.split(self.nodes[node].left, k);
+            self.nodes[node].left = b;
+            self.update(node);
+            (a, t)
+        } else {
+            let (a, b): (Option<usize>, Option<usize>) = self.split(self.nodes[node].right, k-left_blocks-1);
+            self.nodes[node].right = a;
+            self.update(node);
+            (t, b)
+        }
+    }
+
+    fn locate(&self, i: usize) -> (Vec<usize>, usize, usize) {
+        // path from the root to the block holding position i, the block's index and i's offset in it
+        let mut path: Vec<usize> = Vec::new();
+        let mut t: Option<usize> = self.root;
+        let mut i: usize = i;
+        let mut block: usize = 0;
+        while let Some(node) = t {
+            path.push(node);
+            let left: Option<usize> = self.nodes[node].left;
+            let left_len: usize = self.subtree_len(left);
+            let block_len: usize = self.nodes[node].bits.len();
+            if i < left_len {
+                t = left;
+            } else if i < left_len+block_len {
+                return (path, block+self.subtree_blocks(left), i-left_len);
+            } else {
+                i -= left_len+block_len;
+                block += self.subtree_blocks(left)+1;
+                t = self.nodes[node].right;
+            }
+        }
+        panic!("invalid position entry");
+    }
+
+    pub fn get(&self, i: usize) -> bool {
+        if i >= self.len() {
+            panic!("invalid position entry");
+        }
+        let (path, _, offset): (Vec<usize>, usize, usize) = self.locate(i);
+        self.nodes[path[path.len()-1]].bits[offset]
+    }
+
+    pub fn set(&mut self, i: usize, bit: bool) {
+        // overwrite bit i, fixing ones counts along the path
+        if i >= self.len() {
+            panic!("invalid position entry");
+        }
+        let (path, _, offset): (Vec<usize>, usize, usize) = self.locate(i);
+        let leaf: usize = path[path.len()-1];
+        let old: bool = self.nodes[leaf].bits[offset];
+        if old == bit {
+            return;
+        }
+        self.nodes[leaf].bits.set(offset, bit);
+        for node in path {
+            if bit {
+                self.nodes[node].ones += 1;
+            } else {
+                self.nodes[node].ones -= 1;
+            }
+        }
+    }
+
+    pub fn insert(&mut self, i: usize, bit: bool) {
+        // insert bit so it ends up at position i (i = len appends)
+        let len: usize = self.len();
+        if i > len {
+            panic!("invalid position entry");
+        }
+        if len == 0 {
+            let node: usize = self.new_node(bitvec![bit as u8; 1]);
+            self.root = self.merge(self.root, Some(node));
+            return;
+        }
+        // appending goes to the end of the last block
+        let (path, block, offset): (Vec<usize>, usize, usize) = if i == len {
+            let (path, block, offset) = self.locate(len-1);
+            (path, block, offset+1)
+        } else {
+            self.locate(i)
+        };
+        let leaf: usize = path[path.len()-1];
+        self.nodes[leaf].bits.insert(offset, bit);
+        for &node in path.iter() {
+            self.nodes[node].len += 1;
+            self.nodes[node].ones += bit as usize;
+        }
+        if self.nodes[leaf].bits.len() > 2*BLOCK_BITS {
+            // move the back half of the block into a new node right after it
+            let back: BitVec = self.nodes[leaf].bits.split_off(BLOCK_BITS);
+            let new: usize = self.new_node(back);
+            let (before, after): (Option<usize>, Option<usize>) = self.split(self.root, block+1);
+            // leaf and its ancestors are all on the split path, so their totals are recomputed there
+            let before: Option<usize> = self.merge(before, Some(new));
+            self.root = self.merge(before, after);
+        }
+    }
+
+    pub fn remove(&mut self, i: usize) -> bool {
+        // remove and return bit i, dropping its block if it empties
+        if i >= self.len() {
+            panic!("invalid position entry");
+        }
+        let (path, block, offset): (Vec<usize>, usize, usize) = self.locate(i);
+        let leaf: usize = path[path.len()-1];
+        let bit: bool = self.nodes[leaf].bits.remove(offset);
+        for &node in path.iter() {
+            self.nodes[node].len -= 1;
+            self.nodes[node].ones -= bit as usize;
+        }
+        if self.nodes[leaf].bits.is_empty() {
+            let (before, rest): (Option<usize>, Option<usize>) = self.split(self.root, block);
+            let (_, after): (Option<usize>, Option<usize>) = self.split(rest, 1);
+            self.nodes[leaf].bits = BitVec::new();
+            self.free.push(leaf);
+            self.root = self.merge(before, after);
+        }
+        bit
+    }
+
+    pub fn rank1(&self, i: usize) -> usize {
+        // return number of 1s up to position i (exclusive)
+        let mut i: usize = i.min(self.len());
+        let mut rank: usize = 0;
+        let mut t: Option<usize> = self.root;
+        while let Some(node) = t {
+            let left: Option<usize> = self.nodes[node].left;
+            let left_len: usize = self.subtree_len(left);
+            let bits: &BitVec = &self.nodes[node].bits;
+            if i <= left_len {
+                t = left;
+            } else if i <= left_len+bits.len() {
+                return rank + self.subtree_ones(left) + bits[..i-left_len].count_ones();
+            } else {
+                rank += self.subtree_ones(left) + bits.count_ones();
+                i -= left_len+bits.len();
+                t = self.nodes[node].right;
+            }
+        }
+        rank
+    }
+
+    pub fn rank0(&self, i: usize) -> usize {
+        i.min(self.len()) - self.rank1(i)
+    }
+
+    pub fn select1(&self, j: usize) -> usize {
+        // first position p for which rank1(p) = j, i.e. one past the jth 1
+        if j == 0 {
+            return 0;
+        }
+        if j > self.count_ones() {
+            panic!("Select query is too large and does not exist");
+        }
+        let mut j: usize = j;
+        let mut pos: usize = 0;
+        let mut t: Option<usize> = self.root;
+        while let Some(node) = t {
+            let left: Option<usize> = self.nodes[node].left;
+            let left_ones: usize = self.subtree_ones(left);
+            let bits: &BitVec = &self.nodes[node].bits;
+            if j <= left_ones {
+                t = left;
+            } else if j <= left_ones+bits.count_ones() {
+                let offset: usize = bits.iter_ones().nth(j-left_ones-1).unwrap();
+                return pos + self.subtree_len(left) + offset + 1;
+            } else {
+                j -= left_ones+bits.count_ones();
+                pos += self.subtree_len(left)+bits.len();
+                t = self.nodes[node].right;
+            }
+        }
+        unreachable!()
+    }
+
+    pub fn select0(&self, j: usize) -> usize {
+        // first position p for which rank0(p) = j, i.e. one past the jth 0
+        if j == 0 {
+            return 0;
+        }
+        if j > self.len()-self.count_ones() {
+            panic!("Select query is too large and does not exist");
+        }
+        let mut j: usize = j;
+        let mut pos: usize = 0;
+        let mut t: Option<usize> = self.root;
+        while let Some(node) = t {
+            let left: Option<usize> = self.nodes[node].left;
+            let left_zeros: usize = self.subtree_len(left)-self.subtree_ones(left);
+            let bits: &BitVec = &self.nodes[node].bits;
+            if j <= left_zeros {
+                t = left;
+            } else if j <= left_zeros+bits.count_zeros() {
+                let offset: usize = bits.iter_zeros().nth(j-left_zeros-1).unwrap();
+                return pos + self.subtree_len(left) + offset + 1;
+            } else {
+                j -= left_zeros+bits.count_zeros();
+                pos += self.subtree_len(left)+bits.len();
+                t = self.nodes[node].right;
+            }
+        }
+        unreachable!()
+    }
+}
+
+impl BitAccess for DynamicBitVector {
+    fn len(&self) -> usize {
+        DynamicBitVector::len(self)
+    }
+
+    fn get(&self, i: usize) -> bool {
+        DynamicBitVector::get(self, i)
+    }
+}
+
+impl Rank for DynamicBitVector {
+    fn rank1(&self, i: usize) -> usize {
+        DynamicBitVector::rank1(self, i)
+    }
+}
+
+impl Select for DynamicBitVector {
+    fn select1(&self, j: usize) -> usize {
+        DynamicBitVector::select1(self, j)
+    }
+
+    fn select0(&self, j: usize) -> usize {
+        DynamicBitVector::select0(self, j)
+    }
+}
+
+impl FromBits for DynamicBitVector {
+    fn from_bits(bit_v: BitVec) -> DynamicBitVector {
+        // cut bit_v into BLOCK_BITS blocks and append them one at a time
+        let mut dynamic: DynamicBitVector = DynamicBitVector {nodes: Vec::new(), free: Vec::new(), root: None, seed: 0x9e3779b97f4a7c15};
+        for chunk in bit_v.chunks(BLOCK_BITS) {
+            let node: usize = dynamic.new_node(chunk.to_bitvec());
+            dynamic.root = dynamic.merge(dynamic.root, Some(node));
+        }
+        dynamic
+    }
+}
+
+impl SpaceUsage for DynamicBitVector {
+    fn payload(&self) -> usize {
+        self.len()
+    }
+
+    fn overhead(&self) -> usize {
+        // bookkeeping words of the live nodes
+        (self.nodes.len()-self.free.len())*NODE_WORDS*usize::BITS as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    fn check(dynamic: &DynamicBitVector, expected: &[bool]) {
+        // every query against the plain vector, plus the subtree counts and block sizes of every node
+        assert_eq!(dynamic.len(), expected.len());
+        assert_eq!(dynamic.to_bitvec().iter().by_vals().collect::<Vec<bool>>(), expected);
+        let mut ones: usize = 0;
+        for (i, &bit) in expected.iter().enumerate() {
+            assert_eq!(dynamic.get(i), bit);
+            assert_eq!(dynamic.rank1(i), ones);
+            if bit {
+                ones += 1;
+                assert_eq!(dynamic.select1(ones), i+1);
+            } else {
+                assert_eq!(dynamic.select0(i+1-ones), i+1);
+            }
+        }
+        assert_eq!(dynamic.rank1(expected.len()), ones);
+        assert_eq!(dynamic.count_ones(), ones);
+        for (slot, node) in dynamic.nodes.iter().enumerate() {
+            if dynamic.free.contains(&slot) {
+                continue;
+            }
+            assert!(!node.bits.is_empty() && node.bits.len() <= 2*BLOCK_BITS);
+            let (left, right): (Option<usize>, Option<usize>) = (node.left, node.right);
+            assert_eq!(node.len, dynamic.subtree_len(left) + node.bits.len() + dynamic.subtree_len(right));
+            assert_eq!(node.ones, dynamic.subtree_ones(left) + node.bits.count_ones() + dynamic.subtree_ones(right));
+            assert_eq!(node.blocks, dynamic.subtree_blocks(left) + 1 + dynamic.subtree_blocks(right));
+        }
+    }
+
+    #[test]
+    fn random_updates_match_vec() {
+        let mut rng: StdRng = StdRng::seed_from_u64(37);
+        let initial: Vec<bool> = (0..3000).map(|_| rng.gen_bool(0.3)).collect();
+        let mut expected: Vec<bool> = initial.clone();
+        let mut dynamic: DynamicBitVector = DynamicBitVector::from_bits(initial.iter().collect());
+        check(&dynamic, &expected);
+        for round in 0..20 {
+            for _ in 0..500 {
+                let bit: bool = rng.gen_bool(0.5);
+                match rng.gen_range(0..4) {
+                    0 | 1 => {
+                        let i: usize = rng.gen_range(0..=expected.len());
+                        dynamic.insert(i, bit);
+                        expected.insert(i, bit);
+                    }
+                    2 if !expected.is_empty() => {
+                        let i: usize = rng.gen_range(0..expected.len());
+                        assert_eq!(dynamic.remove(i), expected.remove(i));
+                    }
+                    _ if !expected.is_empty() => {
+                        let i: usize = rng.gen_range(0..expected.len());
+                        dynamic.set(i, bit);
+                        expected[i] = bit;
+                    }
+                    _ => {}
+                }
+            }
+            if round%5 == 4 {
+                check(&dynamic, &expected);
+            }
+        }
+        check(&dynamic, &expected);
+        assert_eq!(dynamic.to_rank_support().as_bitvec().iter().by_vals().collect::<Vec<bool>>(), expected);
+    }
+
+    #[test]
+    fn remove_everything_then_insert() {
+        let mut rng: StdRng = StdRng::seed_from_u64(38);
+        let mut expected: Vec<bool> = (0..5000).map(|_| rng.gen_bool(0.5)).collect();
+        let mut dynamic: DynamicBitVector = DynamicBitVector::from_bits(expected.iter().collect());
+        let slots: usize = dynamic.nodes.len();
+        while !expected.is_empty() {
+            let i: usize = rng.gen_range(0..expected.len());
+            assert_eq!(dynamic.remove(i), expected.remove(i));
+        }
+        check(&dynamic, &expected);
+        assert!(dynamic.is_empty() && dynamic.root.is_none());
+        assert_eq!(dynamic.rank1(0), 0);
+        assert_eq!(dynamic.select1(0), 0);
+        // emptied blocks are reused before the arena grows
+        for _ in 0..5000 {
+            let i: usize = rng.gen_range(0..=expected.len());
+            let bit: bool = rng.gen_bool(0.5);
+            dynamic.insert(i, bit);
+            expected.insert(i, bit);
+        }
+        check(&dynamic, &expected);
+        assert!(dynamic.nodes.len() <= slots+expected.len()/BLOCK_BITS);
+    }
+}
@@ -0,0 +1,217 @@
+// Implement an FM-index over a DNA (ACGT) text terminated by $
+// methods: count: number of occurrences of a pattern (backward search)
+            // locate: text positions of every occurrence of a pattern (backward search + sampled suffix array)
+            // size: size of the index in bits
+            // save/load: save/load FM-index to/from file (Persist)
+
+use bitvec::prelude::*;
+use crate::{FromBits, SpaceUsage, BitAccess};
+use serde::{Serialize,Deserialize};
+
+use crate::rank::RankSupport;
+use crate::suffix_array::suffix_array_int;
+use crate::sampled_sa::SampledSuffixArray;
+
+const ALPHABET: [u8; 4] = [b'A', b'C', b'G', b'T'];
+
+#[derive(Serialize,Deserialize, Debug)]
+pub struct FmIndex {
+    occ: Vec<RankSupport>, // one bit-vector per base marking where it occurs in the bwt
+    counts: [usize; 5], // number of symbols smaller than $, A, C, G, T in the text
+    dollar_row: usize, // bwt row holding $
+    sa_samples: SampledSuffixArray // suffix array values at every sample_rate-th text position
+}
+
+fn symbol(c: u8) -> Option<usize> {
+    // map base to its symbol (0 is reserved for $)
+    ALPHABET.iter().position(|&base| base == c).map(|s| s+1)
+}
+
+impl FmIndex {
+    pub fn new(text: &[u8], sample_rate: usize) -> FmIndex {
+        // build bwt and sampled suffix array over text (a trailing $ is optional)
+        let text: &[u8] = text.strip_suffix(b"$").unwrap_or(text);
+        let mut codes: Vec<usize> = text.iter()
+            .map(|&c| symbol(c).unwrap_or_else(|| panic!("invalid base {} in text", c as char)))
+            .collect();
+        codes.push(0);
+        let len: usize = codes.len();
+        let sa: Vec<usize> = suffix_array_int(&codes, ALPHABET.len()+1);
+
+        let mut counts: [usize; 5] = [0; 5];
+        for &c in codes.iter() {
+            counts[c] += 1;
+        }
+        // turn symbol counts into number of smaller symbols
+        let mut sum: usize = 0;
+        for count in counts.iter_mut() {
+            let c: usize = *count;
+            *count = sum;
+            sum += c;
+        }
+
+        let mut bwt_bits: Vec<BitVec> = vec![bitvec![0; len]; 4];
+        let mut dollar_row: usize = 0;
+        for (row, &pos) in sa.iter().enumerate() {
+            if pos == 0 {
+                dollar_row = row;
+            } else {
+                bwt_bits[codes[pos-1]-1].set(row, true);
+            }
+        }
+        let occ: Vec<RankSupport> = bwt_bits.into_iter().map(FromBits::from_bits).collect();
+        let sa_samples: SampledSuffixArray = SampledSuffixArray::new(&sa, sample_rate);
+        FmIndex {occ, counts, dollar_row, sa_samples}
+    }
+
+    pub fn len(&self) -> usize {
+        // length of the bwt (text plus $)
+        self.occ[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lf(&self, row: usize) -> usize {
+        // last-to-first mapping: row of the suffix starting one position earlier in the text
+        if row == self.dollar_row {
+            return 0;
+        }
+        let c: usize = (0..4).find(|&c| self.occ[c].get(row)).unwrap();
+        self.counts[c+1] + self.occ[c].rank1(row)
+    }
+
+    fn backward_search(&self, pattern: &[u8]) -> (usize, usize) {
+        // range of bwt rows [sp, ep) whose suffixes start with pattern
+        if pattern.is_empty() {
+            // every text position, leaving out row 0 (the suffix that is only $)
+            return (self.counts[1], self.len());
+        }
+        let mut sp: usize = 0;
+        let mut ep: usize = self.len();
+        for &c in pattern.iter().rev() {
+            let s: usize = match symbol(c) {
+                Some(s) => s,
+                None => return (0, 0)
+            };
+            sp = self.counts[s] + self.occ[s-1].rank1(sp);
+            ep = self.counts[s] + self.occ[s-1].rank1(ep);
+            if sp >= ep {
+                return (0, 0);
+            }
+        }
+        (sp, ep)
+    }
+
+    pub fn count(&self, pattern: &[u8]) -> usize {
+        // number of occurrences of pattern in the text (the empty pattern occurs at each of the n positions)
+        let (sp, ep): (usize, usize) = self.backward_search(pattern);
+        ep - sp
+    }
+
+    pub fn locate(&self, pattern: &[u8]) -> Vec<usize> {
+        // sorted text positions where pattern occurs
+        let (sp, ep): (usize, usize) = self.backward_search(pattern);
+        let mut positions: Vec<usize> = (sp..ep).map(|row| self.suffix_at(row)).collect();
+        positions.sort_unstable();
+        positions
+    }
+
+    fn suffix_at(&self, row: usize) -> usize {
+        // walk lf until a sampled row, each step moves one position left in the text
+        let mut row: usize = row;
+        let mut steps: usize = 0;
+        loop {
+            if let Some(pos) = self.sa_samples.get(row) {
+                return pos + steps;
+            }
+            row = self.lf(row);
+            steps += 1;
+        }
+    }
+
+    pub fn sampled_sa_overhead(&self) -> usize {
+        // size of the sampled suffix array in bits
+        self.sa_samples.overhead()
+    }
+
+    pub fn size(&self) -> usize {
+        // size of FM-index in bits (occurrence bit-vectors and their rank structures, counts and suffix array samples)
+        self.occ.iter().map(|occ| occ.len() + occ.overhead()).sum::<usize>()
+            + (self.counts.len() + 1)*usize::BITS as usize + self.sa_samples.overhead()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    fn naive_locate(text: &[u8], pattern: &[u8]) -> Vec<usize> {
+        (0..text.len()).filter(|&i| text[i..].starts_with(pattern)).collect()
+    }
+
+    fn random_text(rng: &mut StdRng, len: usize, bases: usize) -> Vec<u8> {
+        (0..len).map(|_| ALPHABET[rng.gen_range(0..bases)]).collect()
+    }
+
+    #[test]
+    fn matches_naive_search() {
+        let mut rng: StdRng = StdRng::seed_from_u64(27);
+        // two bases give long repeated patterns, rows between samples are walked with lf
+        for (len, bases) in [(0, 4), (1, 4), (10, 2), (200, 4), (1000, 2), (3000, 4)] {
+            let text: Vec<u8> = random_text(&mut rng, len, bases);
+            for sample_rate in [1, 5, 32] {
+                let fm: FmIndex = FmIndex::new(&text, sample_rate);
+                assert_eq!(fm.len(), len+1);
+                for _ in 0..200 {
+                    // substrings of the text and random patterns that mostly do not occur
+                    let pattern: Vec<u8> = if len > 0 && rng.gen_bool(0.5) {
+                        let start: usize = rng.gen_range(0..len);
+                        text[start..(start+rng.gen_range(1..=8)).min(len)].to_vec()
+                    } else {
+                        let len: usize = rng.gen_range(1..=6);
+                        random_text(&mut rng, len, 4)
+                    };
+                    let expected: Vec<usize> = naive_locate(&text, &pattern);
+                    assert_eq!(fm.count(&pattern), expected.len(), "count {:?}", pattern);
+                    assert_eq!(fm.locate(&pattern), expected, "locate {:?}", pattern);
+                }
+                assert_eq!(fm.count(b"AN"), 0);
+                assert!(fm.locate(b"N").is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn lf_and_sampled_walk_match_suffix_array() {
+        let mut rng: StdRng = StdRng::seed_from_u64(27);
+        let text: Vec<u8> = random_text(&mut rng, 700, 4);
+        let mut codes: Vec<usize> = text.iter().map(|&c| symbol(c).unwrap()).collect();
+        codes.push(0);
+        let sa: Vec<usize> = suffix_array_int(&codes, ALPHABET.len()+1);
+        let mut rows: Vec<usize> = vec![0; sa.len()];
+        for (row, &pos) in sa.iter().enumerate() {
+            rows[pos] = row;
+        }
+        for sample_rate in [1, 7, 64, 1000] {
+            let fm: FmIndex = FmIndex::new(&text, sample_rate);
+            for (row, &pos) in sa.iter().enumerate() {
+                // lf goes one text position to the left, from position 0 to the $ at the end
+                assert_eq!(fm.lf(row), rows[(pos+sa.len()-1)%sa.len()], "lf({})", row);
+                assert_eq!(fm.suffix_at(row), pos, "suffix_at({}) with rate {}", row, sample_rate);
+            }
+        }
+    }
+
+    #[test]
+    fn empty_pattern_is_every_text_position() {
+        let fm: FmIndex = FmIndex::new(b"ACGTTGCA$", 4);
+        assert_eq!(fm.count(b""), 8);
+        assert_eq!(fm.locate(b""), (0..8).collect::<Vec<usize>>());
+        let empty: FmIndex = FmIndex::new(b"", 4);
+        assert_eq!(empty.count(b""), 0);
+        assert!(empty.locate(b"").is_empty());
+    }
+}
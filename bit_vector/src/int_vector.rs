@@ -0,0 +1,212 @@
+// Implement a bit-packed vector of fixed width integers (n values take n*width bits)
+// methods: get/set: read or write the value at index i
+            // push: append a value
+            // set_width: repack with a different width
+            // iter: iterate over values in order
+            // size: size of packed storage in bits (allocated_size: including unused capacity)
+use serde::{Serialize,Deserialize};
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+#[derive(Serialize,Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct IntVector {
+    data: Vec<u64>,
+    width: usize,
+    len: usize
+}
+
+pub struct Iter<'a> {
+    int_v: &'a IntVector,
+    i: usize
+}
+
+impl IntVector {
+    pub fn new(width: usize) -> IntVector {
+        // empty vector of width-bit values (width 0 stores only 0s)
+        IntVector::with_capacity(width, 0)
+    }
+
+    pub fn with_capacity(width: usize, capacity: usize) -> IntVector {
+        if width > WORD_BITS {
+            panic!("width must be at most {} bits", WORD_BITS);
+        }
+        IntVector {data: Vec::with_capacity((capacity*width).div_ceil(WORD_BITS)), width, len: 0}
+    }
+
+    pub fn from_slice(values: &[usize]) -> IntVector {
+        // pack values with the smallest width that fits the largest one
+        let max_value: usize = values.iter().cloned().max().unwrap_or(0);
+        IntVector::from_slice_with_width(values, IntVector::width_for(max_value))
+    }
+
+    pub fn from_slice_with_width(values: &[usize], width: usize) -> IntVector {
+        let mut int_v: IntVector = IntVector::with_capacity(width, values.len());
+        for &value in values {
+            int_v.push(value);
+        }
+        int_v
+    }
+
+    pub fn width_for(max_value: usize) -> usize {
+        // number of bits needed to store max_value
+        (usize::BITS - max_value.leading_zeros()) as usize
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn mask(&self) -> u64 {
+        if self.width == WORD_BITS { u64::MAX } else { (1 << self.width) - 1 }
+    }
+
+    pub fn get(&self, i: usize) -> usize {
+        // value at index i, may span two words
+        if i >= self.len {
+            panic!("index {} out of range for IntVector of length {}", i, self.len);
+        }
+        if self.width == 0 {
+            return 0;
+        }
+        let bit_pos: usize = i*self.width;
+        let word: usize = bit_pos/WORD_BITS;
+        let offset: usize = bit_pos%WORD_BITS;
+        let mut value: u64 = self.data[word] >> offset;
+        if offset+self.width > WORD_BITS {
+            value |= self.data[word+1] << (WORD_BITS-offset);
+        }
+        (value & self.mask()) as usize
+    }
+
+    pub fn set(&mut self, i: usize, value: usize) {
+        // overwrite value at index i
+        if i >= self.len {
+            panic!("index {} out of range for IntVector of length {}", i, self.len);
+        }
+        if IntVector::width_for(value) > self.width {
+            panic!("value {} does not fit in {} bits", value, self.width);
+        }
+        if self.width == 0 {
+            return;
+        }
+        let value: u64 = value as u64;
+        let bit_pos: usize = i*self.width;
+        let word: usize = bit_pos/WORD_BITS;
+        let offset: usize = bit_pos%WORD_BITS;
+        self.data[word] = (self.data[word] & !(self.mask() << offset)) | (value << offset);
+        if offset+self.width > WORD_BITS {
+            let spill: usize = WORD_BITS-offset;
+            self.data[word+1] = (self.data[word+1] & !(self.mask() >> spill)) | (value >> spill);
+        }
+    }
+
+    pub fn push(&mut self, value: usize) {
+        // append value, growing storage a word at a time
+        self.len += 1;
+        let words: usize = (self.len*self.width).div_ceil(WORD_BITS);
+        if words > self.data.len() {
+            self.data.resize(words, 0);
+        }
+        self.set(self.len-1, value);
+    }
+
+    pub fn set_width(&mut self, width: usize) {
+        // repack every value with a new width (must still fit them all)
+        let values: Vec<usize> = self.iter().collect();
+        *self = IntVector::from_slice_with_width(&values, width);
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {int_v: self, i: 0}
+    }
+
+    pub fn size(&self) -> usize {
+        // size of packed storage in bits
+        self.data.len()*WORD_BITS
+    }
+
+    pub fn allocated_size(&self) -> usize {
+        // bits actually allocated for the packed storage (including unused capacity)
+        self.data.capacity()*WORD_BITS
+    }
+}
+
+impl Iterator for Iter<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.i < self.int_v.len() {
+            self.i += 1;
+            Some(self.int_v.get(self.i-1))
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a IntVector {
+    type Item = usize;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    #[test]
+    fn matches_vec_at_every_width() {
+        let mut rng: StdRng = StdRng::seed_from_u64(33);
+        for width in 0..=WORD_BITS {
+            let max_value: usize = if width == WORD_BITS { usize::MAX } else { (1 << width) - 1 };
+            // 200 values of most widths cross a word boundary many times, the largest value sets every bit
+            let mut expected: Vec<usize> = (0..200).map(|_| rng.gen_range(0..=max_value)).collect();
+            expected[7] = max_value;
+            let mut int_v: IntVector = IntVector::new(width);
+            for &value in &expected {
+                int_v.push(value);
+            }
+            assert_eq!(int_v.len(), expected.len());
+            assert_eq!(int_v.size(), (expected.len()*width).div_ceil(WORD_BITS)*WORD_BITS, "width {}", width);
+            for _ in 0..500 {
+                // overwrite with all 1s or all 0s now and then, so neighbours must be left untouched
+                let i: usize = rng.gen_range(0..expected.len());
+                let value: usize = match rng.gen_range(0..3) {
+                    0 => 0,
+                    1 => max_value,
+                    _ => rng.gen_range(0..=max_value)
+                };
+                int_v.set(i, value);
+                expected[i] = value;
+            }
+            for (i, &value) in expected.iter().enumerate() {
+                assert_eq!(int_v.get(i), value, "width {} index {}", width, i);
+            }
+            assert_eq!(int_v.iter().collect::<Vec<usize>>(), expected);
+            assert_eq!(IntVector::from_slice(&expected).width(), IntVector::width_for(max_value));
+            if width < WORD_BITS {
+                int_v.set_width(width+1);
+                assert_eq!(int_v.iter().collect::<Vec<usize>>(), expected, "repacked from width {}", width);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_value_wider_than_width() {
+        let mut int_v: IntVector = IntVector::new(5);
+        int_v.push(32);
+    }
+}
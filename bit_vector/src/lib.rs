@@ -1,10 +1,243 @@
+// the data structure modules, main.rs only uses some of them for the plots
+pub mod rank;
+pub mod select;
+pub mod sparse;
+pub mod wavelet;
+pub mod fm_index;
+pub mod suffix_array;
+pub mod sampled_sa;
+pub mod bp_tree;
+pub mod louds;
+pub mod trie;
+pub mod int_vector;
+pub mod dac;
+pub mod string_store;
+pub mod rmq;
+pub mod dynamic;
+
 use bitvec::prelude::*;
 use std::io;
+use std::fmt;
+use std::io::{BufWriter, BufReader, Write};
+use std::fs::File;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+// construction from a plain bit-vector
+pub trait FromBits {
+    fn from_bits(bit_v: BitVec) -> Self;
+}
+
+// save/load to a file, every serde type gets it from the blanket impl below (as json)
+pub trait Persist: Sized {
+    fn save(&self, fname: &str) -> Result<(), io::Error>;
+    fn load(fname: &str) -> Result<Self, io::Error>;
+}
+
+// space accounting in bits: payload is the data itself, overhead the index structures on top of it
+pub trait SpaceUsage {
+    fn payload(&self) -> usize;
+    fn overhead(&self) -> usize;
+
+    fn size(&self) -> usize {
+        self.payload() + self.overhead()
+    }
+
+    fn space_breakdown(&self) -> SpaceNode {
+        // named components in bits, types override this with their real parts (tables, headers, slack)
+        SpaceNode::branch("total", vec![SpaceNode::leaf("payload", self.payload()), SpaceNode::leaf("overhead", self.overhead())])
+    }
+}
+
+// one named component of a structure's space in bits, bits of a branch are the sum of its children
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpaceNode {
+    pub name: String,
+    pub bits: usize,
+    pub children: Vec<SpaceNode>
+}
+
+impl SpaceNode {
+    pub fn leaf(name: &str, bits: usize) -> SpaceNode {
+        SpaceNode {name: name.to_string(), bits, children: Vec::new()}
+    }
+
+    pub fn branch(name: &str, children: Vec<SpaceNode>) -> SpaceNode {
+        let bits: usize = children.iter().map(|child| child.bits).sum();
+        SpaceNode {name: name.to_string(), bits, children}
+    }
+
+    pub fn renamed(mut self, name: &str) -> SpaceNode {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn get(&self, path: &str) -> Option<&SpaceNode> {
+        // component at a "/" separated path of child names, e.g. "rank tables/chunk ranks"
+        let mut node: &SpaceNode = self;
+        for name in path.split('/') {
+            node = node.children.iter().find(|child| child.name == name)?;
+        }
+        Some(node)
+    }
+
+    pub fn bits_of(&self, path: &str) -> usize {
+        // bits of a component, 0 if there is no such component
+        self.get(path).map_or(0, |node| node.bits)
+    }
+
+    fn write_indented(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        writeln!(f, "{}{}: {} bits", "  ".repeat(depth), self.name, self.bits)?;
+        for child in self.children.iter() {
+            child.write_indented(f, depth+1)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SpaceNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // one line per component, children indented under their parent
+        self.write_indented(f, 0)
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> Persist for T {
+    fn save(&self, fname: &str) -> Result<(), io::Error> {
+        let file: File = File::create(fname)?;
+        let mut writer: BufWriter<File> = BufWriter::new(file);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn load(fname: &str) -> Result<T, io::Error> {
+        let readfile: File = File::open(fname)?;
+        let reader: BufReader<File> = BufReader::new(readfile);
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
 
-pub trait BitVectorTools {
+// compatibility shim: the original all-in-one trait, for any type with the three traits above
+// existing code keeps working with only `use bit_vector::BitVectorTools;`, new code imports the prelude instead
+// (importing both makes save/load/overhead ambiguous, the shim repeats those names)
+pub trait BitVectorTools: FromBits + Persist + SpaceUsage {
     fn new(bit_v: BitVec) -> Self;
     fn load(fname: &str) -> Self;
     fn overhead(&self) -> usize;
     fn save (&self, fname: &str) -> Result<(), io::Error>;
 
-}
\ No newline at end of file
+}
+
+impl<T: FromBits + Persist + SpaceUsage> BitVectorTools for T {
+    fn new(bit_v: BitVec) -> T {
+        T::from_bits(bit_v)
+    }
+
+    fn load(fname: &str) -> T {
+        // panics like the original, Persist::load returns the error instead
+        <T as Persist>::load(fname).unwrap()
+    }
+
+    fn overhead(&self) -> usize {
+        SpaceUsage::overhead(self)
+    }
+
+    fn save(&self, fname: &str) -> Result<(), io::Error> {
+        Persist::save(self, fname)
+    }
+}
+
+// everything but the shim, for `use bit_vector::prelude::*;`
+pub mod prelude {
+    pub use crate::{FromBits, Persist, SpaceUsage, SpaceNode, BitAccess, Rank, Select};
+}
+
+// read access to the bits of any bit-vector structure
+pub trait BitAccess {
+    fn len(&self) -> usize;
+    fn get(&self, i: usize) -> bool;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// rank1(i)/rank0(i): number of 1s/0s before position i (exclusive)
+pub trait Rank: BitAccess {
+    fn rank1(&self, i: usize) -> usize;
+
+    fn rank0(&self, i: usize) -> usize {
+        i.min(self.len()) - self.rank1(i)
+    }
+}
+
+// select1(j)/select0(j): first position p for which rank1(p) = j (rank0(p) = j), i.e. one past the jth 1 (0)
+pub trait Select: Rank {
+    fn select1(&self, j: usize) -> usize;
+    fn select0(&self, j: usize) -> usize;
+
+    fn succ1(&self, i: usize) -> Option<usize> {
+        // first 1 at or after position i
+        let rank: usize = self.rank1(i);
+        if i >= self.len() || rank == self.rank1(self.len()) {
+            None
+        } else {
+            Some(self.select1(rank+1)-1)
+        }
+    }
+
+    fn pred1(&self, i: usize) -> Option<usize> {
+        // last 1 before position i
+        let rank: usize = self.rank1(i);
+        if rank == 0 {
+            None
+        } else {
+            Some(self.select1(rank)-1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitvec::prelude::*;
+    use crate::prelude::*;
+    use crate::rank::RankSupport;
+    use crate::rmq::Rmq;
+
+    #[test]
+    fn persist_round_trip() {
+        // through the prelude traits (the shim's names are tested below)
+        let rank_support: RankSupport = FromBits::from_bits(bitvec![1, 0, 1, 1, 0, 0, 1]);
+        let fname: String = std::env::temp_dir().join("bit_vector_persist_round_trip.json").to_string_lossy().into_owned();
+        rank_support.save(&fname).unwrap();
+        let loaded: RankSupport = Persist::load(&fname).unwrap();
+        assert_eq!(loaded.as_bitvec(), rank_support.as_bitvec());
+        assert_eq!(loaded.overhead(), rank_support.overhead());
+        // structures without FromBits save and load the same way
+        let rmq: Rmq = Rmq::new(&[3, 1, 4, 1, 5, 9, 2, 6]);
+        rmq.save(&fname).unwrap();
+        let loaded: Rmq = Persist::load(&fname).unwrap();
+        assert_eq!(loaded.query(2, 7), rmq.query(2, 7));
+        std::fs::remove_file(&fname).unwrap();
+        assert!(<RankSupport as Persist>::load(&fname).is_err());
+    }
+
+    mod shim {
+        // only the shim in scope, the way the original code used it
+        use crate::BitVectorTools;
+        use crate::rank::RankSupport;
+        use bitvec::prelude::*;
+
+        #[test]
+        fn shim_methods() {
+            let rank_support: RankSupport = BitVectorTools::new(bitvec![0, 1, 1, 0, 1]);
+            let fname: String = std::env::temp_dir().join("bit_vector_shim_methods.json").to_string_lossy().into_owned();
+            rank_support.save(&fname).unwrap();
+            let loaded: RankSupport = BitVectorTools::load(&fname);
+            assert_eq!(loaded.overhead(), rank_support.overhead());
+            assert_eq!(loaded.rank1(5), 3);
+            std::fs::remove_file(&fname).unwrap();
+        }
+    }
+}
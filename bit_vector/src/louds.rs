@@ -0,0 +1,128 @@
+// Implement a LOUDS (level-order unary degree sequence) tree navigated with rank/select over both bit values
+// bits are "10" for a super root, then for each node in bfs order one 1 per child followed by a 0
+// nodes are identified by their bfs order (root is 0)
+// methods: parent: bfs id of the parent
+            // child: bfs id of the ith child
+            // degree/is_leaf: number of children
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use bitvec::prelude::*;
+use crate::{FromBits, SpaceUsage, BitAccess, Rank};
+use serde::{Serialize,Deserialize};
+
+use crate::select::SelectSupport;
+
+#[derive(Serialize,Deserialize, Debug)]
+pub struct LoudsTree {
+    bits: SelectSupport
+}
+
+impl LoudsTree {
+    pub fn from_adjacency(children: &[Vec<usize>], root: usize) -> (LoudsTree, Vec<usize>) {
+        // build from the children of each node, also returns the bfs id of each input node
+        let mut bfs_ids: Vec<usize> = vec![usize::MAX; children.len()];
+        let mut bit_v: BitVec = bitvec![1, 0];
+        let mut queue: VecDeque<usize> = VecDeque::from([root]);
+        let mut next_id: usize = 0;
+        bfs_ids[root] = next_id;
+        while let Some(node) = queue.pop_front() {
+            for &child in children[node].iter() {
+                if bfs_ids[child] != usize::MAX {
+                    panic!("adjacency list is not a tree");
+                }
+                next_id += 1;
+                bfs_ids[child] = next_id;
+                queue.push_back(child);
+                bit_v.push(true);
+            }
+            bit_v.push(false);
+        }
+        if next_id+1 != children.len() {
+            panic!("adjacency list is not a single tree");
+        }
+        (FromBits::from_bits(bit_v), bfs_ids)
+    }
+
+    pub fn from_parent_array(parents: &[Option<usize>]) -> (LoudsTree, Vec<usize>) {
+        // build from the parent of each node (None for the root), children are ordered by node number
+        let mut children: Vec<Vec<usize>> = vec![Vec::new(); parents.len()];
+        let mut root: Option<usize> = None;
+        for (node, parent) in parents.iter().enumerate() {
+            match parent {
+                Some(p) => children[*p].push(node),
+                None if root.is_none() => root = Some(node),
+                None => panic!("tree has more than one root")
+            }
+        }
+        LoudsTree::from_adjacency(&children, root.expect("tree has no root"))
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.bits.len()/2
+    }
+
+    fn one_position(&self, k: usize) -> usize {
+        // position of the kth (starting at 1) 1
+        self.bits.select1(k)-1
+    }
+
+    fn zero_position(&self, k: usize) -> usize {
+        // position of the kth (starting at 1) 0
+        self.bits.select0(k)-1
+    }
+
+    pub fn children(&self, v: usize) -> Range<usize> {
+        // bfs ids of the children of v (consecutive in bfs order)
+        let start: usize = self.zero_position(v+1)+1;
+        let end: usize = self.zero_position(v+2);
+        let first: usize = self.bits.rank1(start);
+        first..first+(end-start)
+    }
+
+    pub fn degree(&self, v: usize) -> usize {
+        self.children(v).len()
+    }
+
+    pub fn is_leaf(&self, v: usize) -> bool {
+        self.degree(v) == 0
+    }
+
+    pub fn child(&self, v: usize, i: usize) -> Option<usize> {
+        // ith (starting at 0) child of v
+        let children: Range<usize> = self.children(v);
+        if i < children.len() {
+            Some(children.start+i)
+        } else {
+            None
+        }
+    }
+
+    pub fn parent(&self, v: usize) -> Option<usize> {
+        // the 1 of v sits in the block of its parent, which follows the parent's 0s count
+        if v == 0 {
+            return None;
+        }
+        Some(self.bits.rank0(self.one_position(v+1))-1)
+    }
+}
+
+impl FromBits for LoudsTree {
+    fn from_bits(bit_v: BitVec) -> LoudsTree {
+        // n nodes give n 1s (the super root and every non-root node) and n+1 0s
+        if bit_v.count_ones()*2+1 != bit_v.len() || !bit_v.starts_with(bits![1, 0]) {
+            panic!("invalid LOUDS bit-vector");
+        }
+        LoudsTree {bits: SelectSupport::from_bits(bit_v)}
+    }
+}
+
+impl SpaceUsage for LoudsTree {
+    fn payload(&self) -> usize {
+        self.bits.len()
+    }
+
+    fn overhead(&self) -> usize {
+        self.bits.overhead()
+    }
+}
@@ -1,18 +1,15 @@
 use std::time::{Instant,Duration};
 use bitvec::prelude::*;
-use bit_vector::BitVectorTools;
+use bit_vector::{BitVectorTools, SpaceUsage, SpaceNode};
 
 use plotters::prelude::*;
 use rand::{distributions::Bernoulli, distributions::Uniform, Rng};
 use itertools::izip;
-use get_size:: GetSize;
 
-mod rank;
-mod select;
-mod sparse;
-use crate::rank::RankSupport;
-use crate::select::SelectSupport;
-use crate::sparse::{SparseArray, SparseArrayBuilder};
+use bit_vector::rank::RankSupport;
+use bit_vector::select::SelectSupport;
+use bit_vector::sparse::{SparseArray, SparseArrayBuilder};
+use bit_vector::fm_index::FmIndex;
 
 
 fn test_rank(){
@@ -27,9 +24,11 @@ fn test_rank(){
         const B: f64 = 0.1;
         let b_dist:Bernoulli = Bernoulli::new(B).unwrap();
         let mut rng = rand::thread_rng();
-        let bit_v: BitVec = (0..n).map(|_| rng.sample(&b_dist)).collect();
+        let bit_v: BitVec = (0..n).map(|_| rng.sample(b_dist)).collect();
         let bit_vec_rank: RankSupport = BitVectorTools::new(bit_v);
-        let overhead:usize =  bit_vec_rank.overhead();
+        // everything beyond the bits themselves: rank tables, struct and unused capacity
+        let space: SpaceNode = bit_vec_rank.space_breakdown();
+        let overhead: usize = space.bits - space.bits_of("payload");
         let mut total_duration: Duration = Duration::new(0,0);
         //generate random ranks to find and time
         for _ in 0..50 {
@@ -99,12 +98,14 @@ fn test_select(){
         const B: f64 = 0.1;
         let b_dist:Bernoulli = Bernoulli::new(B).unwrap();
         let mut rng = rand::thread_rng();
-        let bit_v: BitVec = (0..n).map(|_| rng.sample(&b_dist)).collect();
+        let bit_v: BitVec = (0..n).map(|_| rng.sample(b_dist)).collect();
         let bit_vec_rank: SelectSupport = BitVectorTools::new(bit_v);
-        let overhead:usize =  bit_vec_rank.overhead();
+        // everything beyond the bits themselves: rank tables, struct and unused capacity
+        let space: SpaceNode = bit_vec_rank.space_breakdown();
+        let overhead: usize = space.bits - space.bits_of("payload");
         let mut total_duration: Duration = Duration::new(0,0);
         // since we have the function panic if select i is too big define max
-        let max_ones: usize = bit_vec_rank.rank_support.bit_v.count_ones(); 
+        let max_ones: usize = bit_vec_rank.as_bitvec().count_ones(); 
         //generate random selects to find and time
         for _ in 0..50 {
             let u_dist: Uniform<usize> = Uniform::new(0, max_ones);
@@ -172,8 +173,8 @@ fn test_sparse(){
     let mut gai_vec2: Vec<Vec<f64>> = Vec::new();
     let mut gio_vec2: Vec<Vec<f64>> = Vec::new();
     let mut nea_vec2: Vec<Vec<f64>> = Vec::new();
-    for B in [0.01,0.05,0.1]{
-        let b_dist:Bernoulli = Bernoulli::new(B).unwrap();
+    for b in [0.01,0.05,0.1]{
+        let b_dist:Bernoulli = Bernoulli::new(b).unwrap();
         let mut rng = rand::thread_rng();
         let mut overhead_vec: Vec<f64> = Vec::new();
         let mut notsparse_vec: Vec<f64> = Vec::new();
@@ -184,20 +185,19 @@ fn test_sparse(){
         let mut nea_vec: Vec<f64> = Vec::new();
         
             for n in n_vec.clone(){
-            let bit_v: BitVec = (0..n as usize).map(|_| rng.sample(&b_dist)).collect();
+            let bit_v: BitVec = (0..n as usize).map(|_| rng.sample(b_dist)).collect();
             //create empty array of size n
             let mut array: SparseArrayBuilder = SparseArrayBuilder::create(n as usize);
             // add items to array
-            let mut count:usize = 0;
-            for item in bit_v{
+            for (count, item) in bit_v.into_iter().enumerate(){
                 if item{ //true add value to SparseArray and position count
                     array.append(item.to_string(), count);
                 }
-                count+=1;
             }
             //finalize array
             let sparse_array: SparseArray = array.finalize();
-            let overhead: usize =  sparse_array.size();
+            // whole footprint: positions with their rank tables, values and metadata
+            let overhead: usize = sparse_array.space_breakdown().bits;
             // let sparsity: usize = sparse_array.num_elem();
             let mut gar_duration: Duration = Duration::new(0,0);
             let mut gai_duration: Duration = Duration::new(0,0);
@@ -214,17 +214,14 @@ fn test_sparse(){
                 let gar: usize = rng.sample(gar_dist);
                 let gai_dist: Uniform<usize> = Uniform::new(0, n as usize);
                 let gai: usize = rng.sample(gai_dist);
-                let mut elem: String = String::from("e");
-                let mut elem2: String = String::from("e");
                 // test get at rank
                 let gar_now = Instant::now();
-                sparse_array.get_at_rank(gar, &mut elem);
+                sparse_array.get_at_rank(gar);
                 gar_duration += gar_now.elapsed();
                 
                 // test get at index
-                let mut elem2: String = String::from("e");
                 let gai_now = Instant::now();
-                sparse_array.get_at_index(gai, &mut elem2);
+                sparse_array.get_at_index(gai);
                 gai_duration += gai_now.elapsed();
                
                 //test get index of 
@@ -286,8 +283,8 @@ fn test_sparse(){
         ).unwrap().label(".1").legend(|(x, y)| Cross::new((x,y), 4.0_f64, ShapeStyle::from(&RED).filled()));
 
         data_ctx.configure_series_labels()
-            .border_style(&BLACK)
-            .background_style(&WHITE.mix(0.8))
+            .border_style(BLACK)
+            .background_style(WHITE.mix(0.8))
             .draw()
             .unwrap();
        
@@ -295,6 +292,74 @@ fn test_sparse(){
     
 }
 
+fn test_sampled_sa(){
+    // test sampled suffix array (build an FM-index over one random DNA text with sample rates 1 to 64,
+    // measure the sampled suffix array and time how long it takes to do 50 locate operations, output 2 plots)
+    const N: usize = 200000;
+    const PATTERN_LEN: usize = 10;
+    let mut rng = rand::thread_rng();
+    let base_dist: Uniform<usize> = Uniform::new(0, 4);
+    let text: Vec<u8> = (0..N).map(|_| b"ACGT"[rng.sample(base_dist)]).collect();
+    let mut rate_vec: Vec<f64> = Vec::new();
+    let mut duration_vec: Vec<f64> = Vec::new();
+    let mut overhead_vec: Vec<f64> = Vec::new();
+    for rate in [1, 2, 4, 8, 16, 32, 64] {
+        let index: FmIndex = FmIndex::new(&text, rate);
+        let overhead: usize = index.sampled_sa_overhead();
+        let mut total_duration: Duration = Duration::new(0,0);
+        //generate random patterns from the text to locate and time
+        for _ in 0..50 {
+            let u_dist: Uniform<usize> = Uniform::new(0, N-PATTERN_LEN);
+            let start: usize = rng.sample(u_dist);
+            let now = Instant::now();
+            let _: Vec<usize> = index.locate(&text[start..start+PATTERN_LEN]);
+            let duration: Duration = now.elapsed();
+            total_duration += duration;
+        }
+        rate_vec.push(rate as f64);
+        overhead_vec.push(overhead as f64);
+        duration_vec.push(total_duration.as_micros() as f64);
+    }
+    // create plots
+    let fname_overhead: &str = "sampled_sa_overhead.png";
+    let fname_duration: &str = "sampled_sa_duration.png";
+
+    let overhead_zip: Vec<(f64, f64)> = rate_vec.iter().cloned().zip(overhead_vec.iter().cloned()).collect();
+    let duration_zip: Vec<(f64, f64)> = rate_vec.iter().cloned().zip(duration_vec.iter().cloned()).collect();
+
+    let rate_max: f64 =  rate_vec.into_iter().reduce(f64::max).unwrap();
+    let overhead_max: f64 =  overhead_vec.into_iter().reduce(f64::max).unwrap();
+    //overhead plot
+    let overhead_area = BitMapBackend::new(fname_overhead, (1000, 800)).into_drawing_area();
+        overhead_area.fill(&WHITE).unwrap();
+    let mut overhead_ctx = ChartBuilder::on(&overhead_area)
+        .set_label_area_size(LabelAreaPosition::Left, 80.0)
+        .set_label_area_size(LabelAreaPosition::Bottom, 50.0)
+        .caption("Sampled suffix array size in bits vs sample rate", ("sans-serif", 40.0))
+        .build_cartesian_2d(0.0..rate_max, 0.0..overhead_max)
+        .unwrap();
+
+    overhead_ctx.configure_mesh().draw().unwrap();
+    overhead_ctx.draw_series(
+        overhead_zip.iter().map(|point| Circle::new(*point, 4.0_f64, ShapeStyle::from(&BLUE).filled())),
+    ).unwrap();
+    //duration plot
+    let duration_area = BitMapBackend::new(fname_duration, (1000, 600)).into_drawing_area();
+    duration_area.fill(&WHITE).unwrap();
+    let duration_max: f64 =  duration_vec.into_iter().reduce(f64::max).unwrap();
+    let mut duration_ctx = ChartBuilder::on(&duration_area)
+        .set_label_area_size(LabelAreaPosition::Left, 80.0)
+        .set_label_area_size(LabelAreaPosition::Bottom, 50.0)
+        .caption("Microsecs for 50 locate ops vs sample rate", ("sans-serif", 40.0))
+        .build_cartesian_2d(0.0..rate_max, 0.0..duration_max)
+        .unwrap();
+
+    duration_ctx.configure_mesh().draw().unwrap();
+    duration_ctx.draw_series(
+        duration_zip.iter().map(|point| Circle::new(*point, 4.0_f64, ShapeStyle::from(&BLUE).filled())),
+    ).unwrap();
+}
+
 fn main(){
     println!("Testing Rank");
     test_rank();
@@ -302,5 +367,7 @@ fn main(){
     test_select();
     println!("Testing sparse array");
     test_sparse();
+    println!("Testing sampled suffix array");
+    test_sampled_sa();
 
 }
\ No newline at end of file
@@ -1,127 +1,739 @@
 // Implement a succinct, constant-time, bit-vector rank operation
 // methods: rank 1- return number of 1s (exclusive)
+            // set: overwrite one bit and repair the chunk/subchunk counts after it
+            // push/extend: append bits, growing the tables with the geometry fixed at construction
+            // rank1_batch: rank1 of many positions, answered in sorted order (optionally on several threads)
+            // count_ones_in/count_zeros_in: number of 1s/0s in any range, with RangeError for bad endpoints
+            // &a & &b, |, ^, !, difference: bitwise set operations a word at a time, giving a new RankSupport
+            // count_ones_range: popcount of a bit range a machine word at a time (popcnt when the cpu has it)
             //overhead: return size of rank data structure in bits
             // save: save rank data structure to file (fname) --and bit vector save function
             // load: load rank data structure from fname () --and bit vector load function
-use std::io;
-use std::io::{BufWriter, BufReader, Write};
-use std::fs::File;
+use std::fmt;
+use std::ops::{Bound, RangeBounds, BitAnd, BitOr, BitXor, Not};
 
-use get_size:: GetSize;
 use bitvec::prelude::*;
 use num_integer::div_floor;
 use serde::{Serialize,Deserialize};
 
-use bit_vector::BitVectorTools;
+use crate::{FromBits, SpaceUsage, SpaceNode, BitAccess, Rank};
+use crate::int_vector::IntVector;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+const WORD_BITS: usize = usize::BITS as usize;
+// most subchunks a chunk may hold: every row of the subchunk table (the last one padded) has this many entries
+const MAX_SUBCHUNKS: usize = 1 << 16;
+
+pub fn count_ones_range(bit_v: &BitVec, start: usize, end: usize) -> usize {
+    // number of 1s in bit_v[start..end], masking the first and last storage words and popcounting the rest whole
+    // (subchunk sizes that are multiples of 64 make every subchunk start word aligned)
+    if start >= end {
+        return 0;
+    }
+    let words: &[usize] = bit_v.as_raw_slice();
+    // a BitVec copied from an unaligned slice can start part way into its first word
+    let head: usize = bit_v.as_bitptr().bit().into_inner() as usize;
+    let (start, end): (usize, usize) = (start+head, end+head);
+    let first: usize = start/WORD_BITS;
+    let last: usize = (end-1)/WORD_BITS;
+    let head_mask: usize = usize::MAX << (start%WORD_BITS);
+    let tail_mask: usize = usize::MAX >> (WORD_BITS-1-(end-1)%WORD_BITS);
+    if first == last {
+        return (words[first] & head_mask & tail_mask).count_ones() as usize;
+    }
+    (words[first] & head_mask).count_ones() as usize
+        + popcount(&words[first+1..last])
+        + (words[last] & tail_mask).count_ones() as usize
+}
+
+fn popcount(words: &[usize]) -> usize {
+    // use the popcnt instruction if the cpu has it (checked once at runtime, cached by std)
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("popcnt") {
+            return unsafe { popcount_popcnt(words) };
+        }
+    }
+    words.iter().map(|word| word.count_ones() as usize).sum()
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "popcnt")]
+unsafe fn popcount_popcnt(words: &[usize]) -> usize {
+    // same loop, compiled with popcnt enabled so count_ones is one instruction
+    words.iter().map(|word| word.count_ones() as usize).sum()
+}
+
+pub fn run_batch<F: Fn(usize) -> usize + Sync>(queries: &[usize], results: &mut [usize], threads: usize, query: F) {
+    // answer queries in sorted order so neighbouring queries touch the same table entries and words,
+    // splitting the sorted queries into one contiguous run per thread, then write results back in input order
+    if queries.len() != results.len() {
+        panic!("need one result slot per query");
+    }
+    let mut order: Vec<usize> = (0..queries.len()).collect();
+    order.sort_unstable_by_key(|&k| queries[k]);
+    let threads: usize = threads.max(1);
+    let run: usize = order.len().div_ceil(threads).max(1);
+    let answers: Vec<usize> = if threads == 1 {
+        order.iter().map(|&k| query(queries[k])).collect()
+    } else {
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = order.chunks(run).map(|part| {
+                let query: &F = &query;
+                scope.spawn(move || part.iter().map(|&k| query(queries[k])).collect::<Vec<usize>>())
+            }).collect();
+            handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+        })
+    };
+    for (&k, answer) in order.iter().zip(answers) {
+        results[k] = answer;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeError {
+    StartAfterEnd {start: usize, end: usize},
+    OutOfBounds {end: usize, len: usize} // end past the bit-vector (or not representable)
+}
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RangeError::StartAfterEnd {start, end} => write!(f, "range starts at {} after its end {}", start, end),
+            RangeError::OutOfBounds {end, len} => write!(f, "range end {} is past the bit-vector length {}", end, len)
+        }
+    }
+}
+
+impl std::error::Error for RangeError {}
+
+// why RankSupportBuilder rejected a chunk/subchunk geometry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockSizeError {
+    ZeroSubchunk,
+    ChunkSmallerThanSubchunk {chunk_size: usize, subchunk_size: usize},
+    TooManySubchunks {chunk_size: usize, subchunk_size: usize}, // more than MAX_SUBCHUNKS per chunk
+    OnlyOneSize // chunk and subchunk sizes are set together or not at all
+}
+
+impl fmt::Display for BlockSizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockSizeError::ZeroSubchunk => write!(f, "subchunk size must be positive"),
+            BlockSizeError::ChunkSmallerThanSubchunk {chunk_size, subchunk_size} =>
+                write!(f, "chunk size {} must be at least the subchunk size {}", chunk_size, subchunk_size),
+            BlockSizeError::TooManySubchunks {chunk_size, subchunk_size} =>
+                write!(f, "chunk size {} holds more than {} subchunks of size {}", chunk_size, MAX_SUBCHUNKS, subchunk_size),
+            BlockSizeError::OnlyOneSize => write!(f, "chunk and subchunk sizes must be set together")
+        }
+    }
+}
+
+impl std::error::Error for BlockSizeError {}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RankSupportBuilder {
+    chunk_size: Option<usize>,
+    subchunk_size: Option<usize>
+}
 
 #[derive(Serialize,Deserialize, Debug)]
 pub struct RankSupport {
-    pub bit_v: BitVec,
-    pub rank_struct: (Vec<usize>, Vec<Vec<usize>>)
+    bit_v: BitVec, // read through BitAccess (or as_bitvec), written through set/push so the tables stay in sync
+    rank_struct: (IntVector, IntVector), // cumulative rank of each chunk, and of each subchunk within its chunk (read with rank_tables)
+    chunk_size: usize, // saved with the tables so a loaded structure keeps its geometry
+    subchunk_size: usize
+}
+
+impl RankSupportBuilder {
+    // choose chunk (superblock) and subchunk (block) sizes in bits, e.g. 4096/512 or 512/64
+    // without them the sizes come from the length: floor(log2(n)^2) and floor(log2(n)/2), rounded up to multiples of 64
+    pub fn new() -> RankSupportBuilder {
+        RankSupportBuilder {chunk_size: None, subchunk_size: None}
+    }
+
+    pub fn chunk_size(mut self, chunk_size: usize) -> RankSupportBuilder {
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+
+    pub fn subchunk_size(mut self, subchunk_size: usize) -> RankSupportBuilder {
+        self.subchunk_size = Some(subchunk_size);
+        self
+    }
+
+    fn validate(&self, len: usize) -> Result<(usize, usize), BlockSizeError> {
+        // sizes to use for a bit-vector of length len
+        match (self.chunk_size, self.subchunk_size) {
+            (None, None) => Ok(RankSupport::block_sizes(len)),
+            (Some(chunk_size), Some(subchunk_size)) => {
+                if subchunk_size == 0 {
+                    Err(BlockSizeError::ZeroSubchunk)
+                } else if chunk_size < subchunk_size {
+                    Err(BlockSizeError::ChunkSmallerThanSubchunk {chunk_size, subchunk_size})
+                } else if chunk_size/subchunk_size > MAX_SUBCHUNKS {
+                    Err(BlockSizeError::TooManySubchunks {chunk_size, subchunk_size})
+                } else {
+                    Ok((chunk_size, subchunk_size))
+                }
+            },
+            _ => Err(BlockSizeError::OnlyOneSize)
+        }
+    }
+
+    pub fn build(self, bit_v: BitVec) -> Result<RankSupport, BlockSizeError> {
+        // rank structure over bit_v with the chosen geometry (an empty bit_v can be filled with push)
+        let (chunk_size, subchunk_size): (usize, usize) = self.validate(bit_v.len())?;
+        let rank_struct: (IntVector, IntVector) = RankSupport::create_rank_structure(&bit_v, chunk_size, subchunk_size);
+        Ok(RankSupport {bit_v, rank_struct, chunk_size, subchunk_size})
+    }
+
+    #[cfg(feature = "parallel")]
+    pub fn build_parallel(self, bit_v: BitVec) -> Result<RankSupport, BlockSizeError> {
+        // same as build, with the chunks counted concurrently (identical tables)
+        let (chunk_size, subchunk_size): (usize, usize) = self.validate(bit_v.len())?;
+        let rank_struct: (IntVector, IntVector) = RankSupport::create_rank_structure_parallel(&bit_v, chunk_size, subchunk_size);
+        Ok(RankSupport {bit_v, rank_struct, chunk_size, subchunk_size})
+    }
 }
 
 impl RankSupport{
+    #[deprecated(note = "use RankSupportBuilder::new().chunk_size(..).subchunk_size(..).build(BitVec::new())")]
+    pub fn with_block_sizes(chunk_size: usize, subchunk_size: usize) -> RankSupport {
+        // empty bit-vector with a fixed geometry, to be filled with push/extend
+        RankSupportBuilder::new().chunk_size(chunk_size).subchunk_size(subchunk_size).build(BitVec::new())
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
 
-    pub fn create_rank_structure(bit_v:BitVec) -> (Vec<usize>, Vec<Vec<usize>>){
+    pub fn create_rank_structure(bit_v: &BitVec, chunk_size: usize, subchunk_size: usize) -> (IntVector, IntVector){
         //create rank structure for chunks and subchunks per jacobson's method
-        // calculate chunk size (going to floor for consistency sake)
-        let chunk_size: usize = (bit_v.len() as f64).log2().powi(2).floor() as usize;
-        let num_chunks: usize = ((bit_v.len() as f64)/(chunk_size as f64)).floor() as usize;
-
-        // calculate subchunk size
-        let subchunk_size: usize = (0.5*(bit_v.len() as f64).log2().floor()) as usize;
-        let num_subchunk: usize = chunk_size/subchunk_size as usize;
+        let num_chunks: usize = bit_v.len()/chunk_size;
+        let num_subchunk: usize = chunk_size/subchunk_size;
         // iterate through chunk slices and get cumulative rank(1) of chunks and subchunks
+        // chunk ranks need log(n) bits each, subchunk ranks (relative to their chunk) only log(chunk_size) bits
         let mut i: usize = 0;
-        let mut rank_vec: Vec<usize> = vec![0; num_chunks+1];
-        let mut rank_array: Vec<Vec<usize>> = Vec::new();
+        let mut rank_vec: IntVector = IntVector::with_capacity(IntVector::width_for(bit_v.len()), num_chunks+1);
+        let mut rank_array: IntVector = IntVector::with_capacity(IntVector::width_for(chunk_size), (num_chunks+1)*(num_subchunk+1));
         let mut sum_rank: usize = 0;
+        rank_vec.push(0); //first entry is 0
 
         while i  < num_chunks { // don't need to look at last (potentially short) subchunk
-            let chunk_rank: usize = (&bit_v[(i*chunk_size)..((i+1)*chunk_size)]).count_ones();
+            let chunk_rank: usize = count_ones_range(bit_v, i*chunk_size, (i+1)*chunk_size);
             sum_rank += chunk_rank;
-            rank_vec[i+1] = sum_rank; //first entry is 0
-            //iterate through previous chunk's subchunks for their rank 
+            rank_vec.push(sum_rank);
+            //iterate through previous chunk's subchunks for their rank (num_subchunk+1 entries per chunk)
             let mut j: usize = 0;
-            let mut sub_vec: Vec<usize> = vec![0; num_subchunk+1];
             let mut sum_subchunk_rank: usize = 0;
+            rank_array.push(0);
             while j < num_subchunk {
-                let subchunk_rank: usize = (&bit_v[(i*chunk_size+j*subchunk_size)..(i*chunk_size+(j+1)*subchunk_size)]).count_ones();
+                let subchunk_rank: usize = count_ones_range(bit_v, i*chunk_size+j*subchunk_size, i*chunk_size+(j+1)*subchunk_size);
                 sum_subchunk_rank += subchunk_rank;
-                sub_vec[j+1] = sum_subchunk_rank;
+                rank_array.push(sum_subchunk_rank);
                 j+=1;
             }
-            //add subchunk ranks to array
-            rank_array.push(sub_vec); 
             i+=1;
         }
-        // add remaining subchunks (remainder will be 0)
+        // add remaining subchunks of the last (partial) chunk, including one ending exactly at bit_v.len()
         let mut j: usize = 0;
-        let mut sub_vec: Vec<usize> = vec![0; num_subchunk+1];
         let mut sum_subchunk_rank: usize = 0;
-        while (i*chunk_size+(j+1)*subchunk_size) < bit_v.len() {
-            let subchunk_rank: usize = (&bit_v[(i*chunk_size+j*subchunk_size)..(i*chunk_size+(j+1)*subchunk_size)]).count_ones();
+        rank_array.push(0);
+        while (i*chunk_size+(j+1)*subchunk_size) <= bit_v.len() {
+            let subchunk_rank: usize = count_ones_range(bit_v, i*chunk_size+j*subchunk_size, i*chunk_size+(j+1)*subchunk_size);
             sum_subchunk_rank += subchunk_rank;
-            sub_vec[j+1] = sum_subchunk_rank;
+            rank_array.push(sum_subchunk_rank);
             j+=1;
         }
-        rank_array.push(sub_vec); 
-        return (rank_vec, rank_array);
+        // pad the last chunk's row so every chunk has num_subchunk+1 entries
+        while j < num_subchunk {
+            rank_array.push(0);
+            j+=1;
+        }
+        (rank_vec, rank_array)
+    }
+
+    #[cfg(feature = "parallel")]
+    pub fn create_rank_structure_parallel(bit_v: &BitVec, chunk_size: usize, subchunk_size: usize) -> (IntVector, IntVector) {
+        // each chunk (including the last, partial one) counts its own 1s and its subchunk row in parallel,
+        // then the chunk counts are prefix summed serially, giving the same tables as create_rank_structure
+        let num_chunks: usize = bit_v.len()/chunk_size;
+        let num_subchunk: usize = chunk_size/subchunk_size;
+        let counts: Vec<(usize, Vec<usize>)> = (0..num_chunks+1).into_par_iter().map(|i| {
+            let chunk_start: usize = i*chunk_size;
+            let chunk_end: usize = (chunk_start+chunk_size).min(bit_v.len());
+            let mut row: Vec<usize> = vec![0; num_subchunk+1];
+            let mut j: usize = 0;
+            while j < num_subchunk && chunk_start+(j+1)*subchunk_size <= chunk_end {
+                row[j+1] = row[j] + count_ones_range(bit_v, chunk_start+j*subchunk_size, chunk_start+(j+1)*subchunk_size);
+                j+=1;
+            }
+            (count_ones_range(bit_v, chunk_start, chunk_end), row)
+        }).collect();
+        let mut rank_vec: IntVector = IntVector::with_capacity(IntVector::width_for(bit_v.len()), num_chunks+1);
+        let mut rank_array: IntVector = IntVector::with_capacity(IntVector::width_for(chunk_size), (num_chunks+1)*(num_subchunk+1));
+        let mut sum_rank: usize = 0;
+        for (chunk_rank, row) in counts {
+            rank_vec.push(sum_rank);
+            sum_rank += chunk_rank;
+            for value in row {
+                rank_array.push(value);
+            }
+        }
+        (rank_vec, rank_array)
     }
 
     pub fn rank1(&self, i: usize) -> usize{
         // return number of 1s in the bit-vector up to position i (exclusive)
-        let chunk_size: usize = (self.bit_v.len() as f64).log2().powi(2).floor() as usize;
-        // calculate subchunk size
-        let subchunk_size: usize = (0.5*(self.bit_v.len() as f64).log2().floor()) as usize;
+        let i: usize = i.min(self.bit_v.len());
+        let (chunk_size, subchunk_size): (usize, usize) = (self.chunk_size, self.subchunk_size);
+        let num_subchunk: usize = chunk_size/subchunk_size;
         // get index of all various chunks
         let chunk_loc: usize = div_floor(i, chunk_size);
         let subchunk_loc: usize =  div_floor(i % chunk_size, subchunk_size);
-        let remainder: isize = (i as isize)-((chunk_size*chunk_loc+subchunk_size*subchunk_loc) as isize);
-        let sum_rank: usize;
-        // sum all cumulative ranks and rank within subchunk together
-        if (chunk_loc*chunk_size+subchunk_loc*subchunk_size)+(remainder as usize) <= self.bit_v.len() {
-            sum_rank = self.rank_struct.0[chunk_loc] 
-                + self.rank_struct.1[chunk_loc][subchunk_loc]
-                + &self.bit_v[(chunk_loc*chunk_size+subchunk_loc*subchunk_size)..(chunk_loc*chunk_size+subchunk_loc*subchunk_size)+(remainder as usize)].count_ones();
+        let start: usize = chunk_loc*chunk_size+subchunk_loc*subchunk_size;
+        // sum all cumulative ranks and rank within subchunk together (looking past the end counts the whole bit-vector)
+        self.rank_struct.0.get(chunk_loc)
+            + self.rank_struct.1.get(chunk_loc*(num_subchunk+1)+subchunk_loc)
+            + count_ones_range(&self.bit_v, start, i)
+    }
+
+    fn block_sizes(len: usize) -> (usize, usize) {
+        // chunk size floor(log2(n)^2) and subchunk size floor(log2(n)/2) for a bit-vector of length n (going to floor for consistency sake)
+        // below n = 4 the subchunk size would be 0, so small bit-vectors use the sizes for n = 4
+        // both are rounded up to whole words, so every chunk and subchunk starts word aligned
+        let n: f64 = len.max(4) as f64;
+        let chunk_size: usize = n.log2().powi(2).floor() as usize;
+        let subchunk_size: usize = (0.5*n.log2().floor()) as usize;
+        (chunk_size.next_multiple_of(WORD_BITS), subchunk_size.next_multiple_of(WORD_BITS))
+    }
+
+    pub fn as_bitvec(&self) -> &BitVec {
+        &self.bit_v
+    }
+
+    pub fn rank_tables(&self) -> &(IntVector, IntVector) {
+        // chunk and subchunk counts, read only so they can't get out of sync with the bits
+        &self.rank_struct
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    pub fn subchunk_size(&self) -> usize {
+        self.subchunk_size
+    }
+
+    pub fn push(&mut self, bit: bool) {
+        // append bit, adding a subchunk count when a subchunk fills and a chunk count (plus a new row) when a chunk fills
+        let (chunk_size, subchunk_size): (usize, usize) = (self.chunk_size, self.subchunk_size);
+        let num_subchunk: usize = chunk_size/subchunk_size;
+        let p: usize = self.bit_v.len();
+        self.bit_v.push(bit);
+        let chunk_loc: usize = p/chunk_size;
+        let chunk_start: usize = chunk_loc*chunk_size;
+        let offset: usize = p+1-chunk_start;
+        if offset.is_multiple_of(subchunk_size) && offset/subchunk_size <= num_subchunk {
+            let s: usize = offset/subchunk_size;
+            let entry: usize = chunk_loc*(num_subchunk+1)+s;
+            let sum: usize = self.rank_struct.1.get(entry-1) + count_ones_range(&self.bit_v, p+1-subchunk_size, p+1);
+            self.rank_struct.1.set(entry, sum);
+        }
+        if offset == chunk_size {
+            let sum: usize = self.rank_struct.0.get(chunk_loc) + count_ones_range(&self.bit_v, chunk_start, p+1);
+            // chunk ranks go up to n (set can raise any of them that far), widen them as n grows
+            let width: usize = IntVector::width_for(self.bit_v.len());
+            if width > self.rank_struct.0.width() {
+                self.rank_struct.0.set_width(width);
+            }
+            self.rank_struct.0.push(sum);
+            for _ in 0..num_subchunk+1 {
+                self.rank_struct.1.push(0);
+            }
+        }
+    }
+
+    pub fn extend<I: IntoIterator<Item = bool>>(&mut self, bits: I) {
+        // push every bit in order
+        for bit in bits {
+            self.push(bit);
+        }
+    }
+
+    pub fn set(&mut self, i: usize, bit: bool) {
+        // overwrite bit i and fix the cumulative counts that include it
+        // later chunks (O(n/chunk_size)) and later subchunks of the same chunk (O(chunk_size/subchunk_size))
+        if i >= self.bit_v.len() {
+            panic!("invalid position entry");
+        }
+        if self.bit_v[i] == bit {
+            return;
+        }
+        self.bit_v.set(i, bit);
+        let (chunk_size, subchunk_size): (usize, usize) = (self.chunk_size, self.subchunk_size);
+        let num_subchunk: usize = chunk_size/subchunk_size;
+        let chunk_loc: usize = div_floor(i, chunk_size);
+        let subchunk_loc: usize = div_floor(i % chunk_size, subchunk_size);
+        let adjust = |value: usize| if bit { value+1 } else { value-1 };
+        for c in chunk_loc+1..self.rank_struct.0.len() {
+            self.rank_struct.0.set(c, adjust(self.rank_struct.0.get(c)));
+        }
+        // the last chunk's row is padded with 0s past the end of the bit-vector, leave those alone
+        let row: usize = chunk_loc*(num_subchunk+1);
+        let mut s: usize = subchunk_loc+1;
+        while s <= num_subchunk && chunk_loc*chunk_size+s*subchunk_size <= self.bit_v.len() {
+            self.rank_struct.1.set(row+s, adjust(self.rank_struct.1.get(row+s)));
+            s+=1;
+        }
+    }
+
+    fn resolve_range<R: RangeBounds<usize>>(&self, range: R) -> Result<(usize, usize), RangeError> {
+        // turn any range form into [start, end) and check it against the bit-vector
+        let len: usize = self.bit_v.len();
+        let start: usize = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start.checked_add(1).ok_or(RangeError::OutOfBounds {end: usize::MAX, len})?,
+            Bound::Unbounded => 0
+        };
+        let end: usize = match range.end_bound() {
+            Bound::Included(&end) => end.checked_add(1).ok_or(RangeError::OutOfBounds {end: usize::MAX, len})?,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => len
+        };
+        if end > len {
+            Err(RangeError::OutOfBounds {end, len})
+        } else if start > end {
+            Err(RangeError::StartAfterEnd {start, end})
         } else {
-            // looking for rank bigger than size of bit vector
-            sum_rank =  self.rank_struct.0[chunk_loc] 
-                + self.rank_struct.1[chunk_loc][subchunk_loc]
-                + &self.bit_v[(chunk_loc*chunk_size+subchunk_loc*subchunk_size)..].count_ones();
+            Ok((start, end))
         }
-        return sum_rank;
+    }
+
+    pub fn count_ones_in<R: RangeBounds<usize>>(&self, range: R) -> Result<usize, RangeError> {
+        // number of 1s in range (a..b, a..=b, ..b, a.., ..)
+        let (start, end): (usize, usize) = self.resolve_range(range)?;
+        Ok(self.rank1(end) - self.rank1(start))
+    }
+
+    pub fn count_zeros_in<R: RangeBounds<usize>>(&self, range: R) -> Result<usize, RangeError> {
+        let (start, end): (usize, usize) = self.resolve_range(range)?;
+        Ok(self.rank0(end) - self.rank0(start))
+    }
+
+    fn words(&self) -> Vec<usize> {
+        // storage words of bit_v with bit 0 at the start of the first word
+        if self.bit_v.as_bitptr().bit().into_inner() == 0 {
+            self.bit_v.as_raw_slice().to_vec()
+        } else {
+            let mut aligned: BitVec = BitVec::with_capacity(self.bit_v.len());
+            aligned.extend_from_bitslice(&self.bit_v);
+            aligned.into_vec()
+        }
+    }
+
+    fn combine<F: Fn(usize, usize) -> usize>(&self, other: &RankSupport, op: F) -> RankSupport {
+        // apply op to each pair of storage words and index the result with this structure's geometry
+        if self.bit_v.len() != other.bit_v.len() {
+            panic!("bit-vectors must have the same length");
+        }
+        let words: Vec<usize> = self.words().into_iter().zip(other.words()).map(|(a, b)| op(a, b)).collect();
+        self.with_words(words)
+    }
+
+    fn with_words(&self, words: Vec<usize>) -> RankSupport {
+        let mut bit_v: BitVec = BitVec::from_vec(words);
+        bit_v.truncate(self.bit_v.len());
+        RankSupportBuilder::new().chunk_size(self.chunk_size).subchunk_size(self.subchunk_size).build(bit_v).unwrap()
+    }
+
+    pub fn difference(&self, other: &RankSupport) -> RankSupport {
+        // bits set here but not in other
+        self.combine(other, |a, b| a & !b)
+    }
+
+    pub fn rank1_batch(&self, queries: &[usize], results: &mut [usize]) {
+        // results[k] = rank1(queries[k])
+        self.rank1_batch_threads(queries, results, 1);
+    }
+
+    pub fn rank1_batch_threads(&self, queries: &[usize], results: &mut [usize], threads: usize) {
+        run_batch(queries, results, threads, |i| self.rank1(i));
+    }
+
+    pub fn rank0(&self, i: usize) -> usize {
+        // return number of 0s in the bit-vector up to position i (exclusive)
+        i.min(self.bit_v.len()) - self.rank1(i)
     }
 }
 
-impl BitVectorTools for RankSupport {
+impl BitAccess for RankSupport {
+    fn len(&self) -> usize {
+        self.bit_v.len()
+    }
+
+    fn get(&self, i: usize) -> bool {
+        self.bit_v[i]
+    }
+}
+
+impl Rank for RankSupport {
+    fn rank1(&self, i: usize) -> usize {
+        RankSupport::rank1(self, i)
+    }
+
+    fn rank0(&self, i: usize) -> usize {
+        RankSupport::rank0(self, i)
+    }
+}
+
+impl BitAnd for &RankSupport {
+    type Output = RankSupport;
+
+    fn bitand(self, other: &RankSupport) -> RankSupport {
+        self.combine(other, |a, b| a & b)
+    }
+}
+
+impl BitOr for &RankSupport {
+    type Output = RankSupport;
+
+    fn bitor(self, other: &RankSupport) -> RankSupport {
+        self.combine(other, |a, b| a | b)
+    }
+}
 
-    fn new(bit_v: BitVec) -> RankSupport {
+impl BitXor for &RankSupport {
+    type Output = RankSupport;
+
+    fn bitxor(self, other: &RankSupport) -> RankSupport {
+        self.combine(other, |a, b| a ^ b)
+    }
+}
+
+impl Not for &RankSupport {
+    type Output = RankSupport;
+
+    fn not(self) -> RankSupport {
+        // bits past the end are dropped by truncating to the same length
+        self.with_words(self.words().into_iter().map(|a| !a).collect())
+    }
+}
+
+impl FromBits for RankSupport {
+    fn from_bits(bit_v: BitVec) -> RankSupport {
         //new bit vector so create the rank structure
-        let bit_vec_rank:RankSupport = RankSupport{bit_v: bit_v.clone(), rank_struct:RankSupport::create_rank_structure(bit_v)};
-        // bit_vec_rank.rank_struct = Some(bit_vec_rank.create_rank_structure());
-        return bit_vec_rank;
+        // default geometry from the length, which is always valid
+        RankSupportBuilder::new().build(bit_v).unwrap()
     }
+}
 
-    fn load(fname: &str) -> RankSupport{
-        // load bitvector and rank data structure from file name
-        let readfile = File::open(fname).unwrap();
-        let reader: BufReader<File> = BufReader::new(readfile);
-        let bit_vec_rank: RankSupport = serde_json::from_reader(reader).unwrap();
-        return bit_vec_rank;
+impl SpaceUsage for RankSupport {
+    fn payload(&self) -> usize {
+        self.bit_v.len()
     }
-    
+
     fn overhead(&self) -> usize {
-        return self.rank_struct.0.get_heap_size()*8 + self.rank_struct.1.get_heap_size()*8;
+        self.rank_struct.0.size() + self.rank_struct.1.size()
     }
-    
-    fn save(&self, fname: &str) -> Result<(), io::Error>{
-        // save bitvector and rank data structure to file name
-        let file: File = File::create(fname)?;
-        let mut writer: BufWriter<File> = BufWriter::new(file);
-        serde_json::to_writer(&mut writer, &self)?;
-        writer.flush()?;
-        Ok(())
+
+    fn space_breakdown(&self) -> SpaceNode {
+        // the bits, the two rank tables, and the struct itself plus unused capacity of its buffers
+        let (chunk_ranks, subchunk_ranks): (&IntVector, &IntVector) = (&self.rank_struct.0, &self.rank_struct.1);
+        let slack: usize = (self.bit_v.capacity()-self.bit_v.len())
+            + (chunk_ranks.allocated_size()-chunk_ranks.size()) + (subchunk_ranks.allocated_size()-subchunk_ranks.size());
+        SpaceNode::branch("rank support", vec![
+            SpaceNode::leaf("payload", self.bit_v.len()),
+            SpaceNode::branch("rank tables", vec![
+                SpaceNode::leaf("chunk ranks", chunk_ranks.size()),
+                SpaceNode::leaf("subchunk ranks", subchunk_ranks.size())
+            ]),
+            SpaceNode::branch("metadata", vec![
+                SpaceNode::leaf("struct", std::mem::size_of::<RankSupport>()*u8::BITS as usize),
+                SpaceNode::leaf("unused capacity", slack)
+            ])
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    fn check_ranks(rank_support: &RankSupport, expected: &[bool]) {
+        let mut ones: usize = 0;
+        for (i, &bit) in expected.iter().enumerate() {
+            assert_eq!(rank_support.rank1(i), ones, "rank1({})", i);
+            ones += bit as usize;
+        }
+        assert_eq!(rank_support.rank1(expected.len()), ones);
+    }
+
+    #[test]
+    fn builder_rejects_bad_geometry() {
+        let bits = || BitVec::repeat(false, 100);
+        assert_eq!(RankSupportBuilder::new().chunk_size(64).subchunk_size(0).build(bits()).unwrap_err(), BlockSizeError::ZeroSubchunk);
+        assert_eq!(RankSupportBuilder::new().chunk_size(64).subchunk_size(128).build(bits()).unwrap_err(),
+            BlockSizeError::ChunkSmallerThanSubchunk {chunk_size: 64, subchunk_size: 128});
+        assert_eq!(RankSupportBuilder::new().chunk_size(64).build(bits()).unwrap_err(), BlockSizeError::OnlyOneSize);
+        assert_eq!(RankSupportBuilder::new().chunk_size(1 << 40).subchunk_size(1).build(bits()).unwrap_err(),
+            BlockSizeError::TooManySubchunks {chunk_size: 1 << 40, subchunk_size: 1});
+        assert!(RankSupportBuilder::new().chunk_size(64 << 16).subchunk_size(64).build(bits()).is_ok());
+        let rank_support: RankSupport = RankSupportBuilder::new().chunk_size(512).subchunk_size(64).build(bits()).unwrap();
+        assert_eq!((rank_support.chunk_size(), rank_support.subchunk_size()), (512, 64));
+    }
+
+    #[test]
+    fn default_geometry_is_word_aligned() {
+        for len in [0, 1, 3, 4, 100, 1000, 1_000_000] {
+            let (chunk_size, subchunk_size): (usize, usize) = RankSupport::block_sizes(len);
+            assert!(chunk_size.is_multiple_of(64) && subchunk_size.is_multiple_of(64) && chunk_size >= subchunk_size);
+        }
+        let mut rng: StdRng = StdRng::seed_from_u64(41);
+        let expected: Vec<bool> = (0..10_000).map(|_| rng.gen_bool(0.4)).collect();
+        check_ranks(&RankSupport::from_bits(expected.iter().collect()), &expected);
     }
 
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_tables_match_serial() {
+        let mut rng: StdRng = StdRng::seed_from_u64(43);
+        let lengths: Vec<usize> = vec![0, 1, 2, 3, 4, 5, 63, 64, 65, 1000, 4095, 4096, 4097, 100_003];
+        for &len in lengths.iter() {
+            let bit_v: BitVec = (0..len).map(|_| rng.gen_bool(0.5)).collect();
+            let builders: [RankSupportBuilder; 3] = [
+                RankSupportBuilder::new(),
+                RankSupportBuilder::new().chunk_size(4096).subchunk_size(512),
+                RankSupportBuilder::new().chunk_size(100).subchunk_size(7)
+            ];
+            for builder in builders {
+                let serial: RankSupport = builder.build(bit_v.clone()).unwrap();
+                let parallel: RankSupport = builder.build_parallel(bit_v.clone()).unwrap();
+                assert_eq!(serial.rank_tables(), parallel.rank_tables(), "length {} with {:?}", len, builder);
+            }
+        }
+    }
+
+    #[test]
+    fn set_after_push() {
+        // chunk counts pushed while all 0 still have to fit the 1s set later
+        let mut rank_support: RankSupport = RankSupportBuilder::new().chunk_size(512).subchunk_size(64).build(BitVec::new()).unwrap();
+        let mut expected: Vec<bool> = vec![false; 2048];
+        rank_support.extend(expected.iter().cloned());
+        rank_support.set(0, true);
+        expected[0] = true;
+        check_ranks(&rank_support, &expected);
+        let mut small: RankSupport = RankSupport::from_bits(BitVec::new());
+        small.extend([false; 8]);
+        small.set(0, true);
+        small.set(5, true);
+        check_ranks(&small, &[true, false, false, false, false, true, false, false]);
+    }
+
+    #[test]
+    fn random_push_and_set() {
+        let mut rng: StdRng = StdRng::seed_from_u64(39);
+        let mut rank_support: RankSupport = RankSupport::from_bits(BitVec::new());
+        let mut expected: Vec<bool> = Vec::new();
+        for _ in 0..20 {
+            for _ in 0..300 {
+                let bit: bool = rng.gen_bool(0.2);
+                rank_support.push(bit);
+                expected.push(bit);
+            }
+            for _ in 0..100 {
+                let i: usize = rng.gen_range(0..expected.len());
+                let bit: bool = rng.gen_bool(0.8);
+                rank_support.set(i, bit);
+                expected[i] = bit;
+            }
+            check_ranks(&rank_support, &expected);
+        }
+    }
+
+    #[test]
+    fn set_operations_match_vec() {
+        let mut rng: StdRng = StdRng::seed_from_u64(47);
+        for len in [0, 1, 63, 64, 65, 200, 1000] {
+            let a_bits: BitVec = (0..len).map(|_| rng.gen_bool(0.5)).collect();
+            // copied 5 bits into a longer vector, so its storage words start part way in
+            let big: BitVec = (0..len+5).map(|_| rng.gen_bool(0.3)).collect();
+            let b_bits: BitVec = big[5..].to_bitvec();
+            assert_eq!(b_bits.as_bitptr().bit().into_inner(), 5);
+            let a_vec: Vec<bool> = a_bits.iter().by_vals().collect();
+            let b_vec: Vec<bool> = b_bits.iter().by_vals().collect();
+            let (a, b): (RankSupport, RankSupport) = (RankSupport::from_bits(a_bits), RankSupport::from_bits(b_bits));
+            let check = |result: RankSupport, op: fn(bool, bool) -> bool| {
+                let expected: Vec<bool> = a_vec.iter().zip(&b_vec).map(|(&x, &y)| op(x, y)).collect();
+                assert_eq!(result.as_bitvec().iter().by_vals().collect::<Vec<bool>>(), expected, "length {}", len);
+                check_ranks(&result, &expected);
+            };
+            check(&a & &b, |x, y| x && y);
+            check(&a | &b, |x, y| x || y);
+            check(&a ^ &b, |x, y| x != y);
+            check(a.difference(&b), |x, y| x && !y);
+            check(b.difference(&a), |x, y| y && !x);
+            check(!&b, |_, y| !y);
+        }
+    }
+
+    #[test]
+    fn range_counts_match_vec() {
+        let mut rng: StdRng = StdRng::seed_from_u64(46);
+        let bits: Vec<bool> = (0..300).map(|_| rng.gen_bool(0.4)).collect();
+        let rank_support: RankSupport = RankSupport::from_bits(bits.iter().collect());
+        let len: usize = bits.len();
+        let ones = |a: usize, b: usize| bits[a..b].iter().filter(|&&bit| bit).count();
+        for _ in 0..200 {
+            let a: usize = rng.gen_range(0..len);
+            let b: usize = rng.gen_range(a..len);
+            assert_eq!(rank_support.count_ones_in(a..b), Ok(ones(a, b)), "{}..{}", a, b);
+            assert_eq!(rank_support.count_zeros_in(a..b), Ok(b-a-ones(a, b)), "{}..{}", a, b);
+            assert_eq!(rank_support.count_ones_in(a..=b), Ok(ones(a, b+1)));
+            assert_eq!(rank_support.count_ones_in(..b), Ok(ones(0, b)));
+            assert_eq!(rank_support.count_ones_in(..=b), Ok(ones(0, b+1)));
+            assert_eq!(rank_support.count_ones_in(a..), Ok(ones(a, len)));
+            assert_eq!(rank_support.count_ones_in((Bound::Excluded(a), Bound::Included(b))), Ok(ones(a+1, b+1)));
+        }
+        assert_eq!(rank_support.count_ones_in(..), Ok(ones(0, len)));
+        assert_eq!(rank_support.count_zeros_in(..), Ok(len-ones(0, len)));
+        assert_eq!(rank_support.count_ones_in(..=len-1), Ok(ones(0, len)));
+        assert_eq!(rank_support.count_ones_in(len..len), Ok(0));
+        assert_eq!(rank_support.count_zeros_in(len..), Ok(0));
+        assert_eq!(rank_support.count_ones_in(..len+1), Err(RangeError::OutOfBounds {end: len+1, len}));
+        assert_eq!(rank_support.count_zeros_in(..=len), Err(RangeError::OutOfBounds {end: len+1, len}));
+        assert_eq!(rank_support.count_ones_in(len+1..), Err(RangeError::StartAfterEnd {start: len+1, end: len}));
+        assert_eq!(rank_support.count_ones_in((Bound::Included(5), Bound::Excluded(3))), Err(RangeError::StartAfterEnd {start: 5, end: 3}));
+        assert_eq!(rank_support.count_ones_in((Bound::Excluded(usize::MAX), Bound::Unbounded)),
+            Err(RangeError::OutOfBounds {end: usize::MAX, len}));
+        assert_eq!(rank_support.count_ones_in(..=usize::MAX), Err(RangeError::OutOfBounds {end: usize::MAX, len}));
+        // an empty bit-vector only has empty ranges
+        let empty: RankSupport = RankSupport::from_bits(BitVec::new());
+        assert_eq!(empty.count_ones_in(..), Ok(0));
+        assert_eq!(empty.count_zeros_in(0..0), Ok(0));
+        assert_eq!(empty.count_ones_in(0..=0), Err(RangeError::OutOfBounds {end: 1, len: 0}));
+    }
+
+    #[test]
+    fn batch_results_in_input_order() {
+        let mut rng: StdRng = StdRng::seed_from_u64(42);
+        let bits: Vec<bool> = (0..5000).map(|_| rng.gen_bool(0.5)).collect();
+        let rank_support: RankSupport = RankSupport::from_bits(bits.iter().collect());
+        // unsorted queries with repeats, more threads than queries and more queries than threads
+        let queries: Vec<usize> = (0..1000).map(|_| rng.gen_range(0..=bits.len())).collect();
+        for threads in [1, 2, 3, 8, 2000] {
+            let mut results: Vec<usize> = vec![0; queries.len()];
+            rank_support.rank1_batch_threads(&queries, &mut results, threads);
+            for (&i, &result) in queries.iter().zip(&results) {
+                assert_eq!(result, rank_support.rank1(i), "rank1({}) with {} threads", i, threads);
+            }
+            let mut results: Vec<usize> = vec![0; 3];
+            run_batch(&[7, 2, 5], &mut results, threads, |i| 10*i);
+            assert_eq!(results, vec![70, 20, 50]);
+            run_batch(&[], &mut [], threads, |i| i);
+            rank_support.rank1_batch_threads(&[], &mut [], threads);
+        }
+        rank_support.rank1_batch(&[], &mut []);
+    }
 }
@@ -0,0 +1,180 @@
+// Implement a succinct range minimum query structure (Fischer-Heun style)
+// the array is encoded as the balanced parentheses of its 2d-min-heap (2n+2 bits), and a minimum over
+// array positions becomes a minimum excess over parentheses, answered with a sparse table of block minima
+// queries take constant time: the parentheses of i and j are found with select samples, and the (at most three)
+// blocks scanned are walked a byte at a time with a lookup table
+// methods: query: position of the (leftmost) minimum in array positions [i, j]
+            // size: size of RMQ in bits
+            // save/load: save/load RMQ to/from file (Persist)
+
+use bitvec::prelude::*;
+use crate::{FromBits, SpaceUsage};
+use serde::{Serialize,Deserialize};
+
+use crate::bp_tree::{BpTree, BLOCK_SIZE};
+use crate::int_vector::IntVector;
+use crate::select::SelectSamples;
+
+#[derive(Serialize,Deserialize, Debug)]
+pub struct Rmq {
+    bp: BpTree,
+    opens: SelectSamples, // constant time select1 over the parentheses, to find the open parenthesis of a value
+    sparse: Vec<IntVector>, // sparse[k][b]: block with the smallest minimum excess (rightmost on ties) in blocks [b, b+2^(k+1))
+    len: usize
+}
+
+impl Rmq {
+    pub fn new<T: Ord>(values: &[T]) -> Rmq {
+        // 2d-min-heap: for each value close the stack entries larger than it, then open it
+        let mut bit_v: BitVec = BitVec::with_capacity(2*values.len()+2);
+        let mut stack: Vec<usize> = Vec::new();
+        bit_v.push(true);
+        for (i, value) in values.iter().enumerate() {
+            while let Some(&top) = stack.last() {
+                if values[top] > *value {
+                    stack.pop();
+                    bit_v.push(false);
+                } else {
+                    break;
+                }
+            }
+            stack.push(i);
+            bit_v.push(true);
+        }
+        for _ in 0..stack.len()+1 {
+            bit_v.push(false);
+        }
+        let bp: BpTree = FromBits::from_bits(bit_v);
+        let sparse: Vec<IntVector> = Rmq::create_sparse_table(&bp);
+        let opens: SelectSamples = SelectSamples::new(bp.as_bitvec());
+        Rmq {bp, opens, sparse, len: values.len()}
+    }
+
+    fn create_sparse_table(bp: &BpTree) -> Vec<IntVector> {
+        // doubling: the best block of a range is the better of the best blocks of its two halves
+        let num_blocks: usize = bp.num_blocks();
+        let width: usize = IntVector::width_for(num_blocks);
+        let mut sparse: Vec<IntVector> = Vec::new();
+        let mut span: usize = 1;
+        while 2*span <= num_blocks {
+            let mut level: IntVector = IntVector::with_capacity(width, num_blocks-2*span+1);
+            for b in 0..=num_blocks-2*span {
+                let left: usize = if span == 1 { b } else { sparse.last().unwrap().get(b) };
+                let right: usize = if span == 1 { b+1 } else { sparse.last().unwrap().get(b+span) };
+                level.push(Rmq::better_block(bp, left, right));
+            }
+            sparse.push(level);
+            span *= 2;
+        }
+        sparse
+    }
+
+    fn better_block(bp: &BpTree, left: usize, right: usize) -> usize {
+        // right block wins ties so the rightmost minimum is found
+        if bp.block_min_excess(right) <= bp.block_min_excess(left) { right } else { left }
+    }
+
+    fn best_block(&self, x: usize, y: usize) -> usize {
+        // block with the smallest minimum in blocks [x, y], two overlapping table lookups
+        if x == y {
+            return x;
+        }
+        let k: usize = (usize::BITS - 1 - (y-x+1).leading_zeros()) as usize;
+        let table: &IntVector = &self.sparse[k-1];
+        Rmq::better_block(&self.bp, table.get(x), table.get(y+1-(1 << k)))
+    }
+
+    fn min_excess(&self, a: usize, b: usize) -> (usize, isize) {
+        // rightmost position of the minimum excess in parentheses [a, b]
+        let block_a: usize = a/BLOCK_SIZE;
+        let block_b: usize = b/BLOCK_SIZE;
+        if block_a == block_b {
+            return self.bp.scan_min_excess(a, b);
+        }
+        // later candidates win ties
+        let mut best: (usize, isize) = self.bp.scan_min_excess(a, (block_a+1)*BLOCK_SIZE-1);
+        if block_b > block_a+1 {
+            let block: usize = self.best_block(block_a+1, block_b-1);
+            if self.bp.block_min_excess(block) <= best.1 {
+                best = self.bp.scan_min_excess(block*BLOCK_SIZE, (block+1)*BLOCK_SIZE-1);
+            }
+        }
+        let last: (usize, isize) = self.bp.scan_min_excess(block_b*BLOCK_SIZE, b);
+        if last.1 <= best.1 {
+            best = last;
+        }
+        best
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn query(&self, i: usize, j: usize) -> usize {
+        // position of the leftmost minimum value in [i, j] (inclusive)
+        if i > j || j >= self.len {
+            panic!("invalid range [{}, {}] for RMQ of length {}", i, j, self.len);
+        }
+        if i == j {
+            return i;
+        }
+        // open parenthesis of value k is the (k+2)th one (the first is the heap root)
+        let open_i: usize = self.opens.select1(self.bp.as_bitvec(), i+2)-1;
+        let open_j: usize = self.opens.select1(self.bp.as_bitvec(), j+2)-1;
+        let (pos, excess): (usize, isize) = self.min_excess(open_i, open_j);
+        if excess == self.bp.excess(open_i) {
+            // nothing after i closed it, so i is the minimum
+            i
+        } else {
+            // the minimum closed everything before it and opens right after the lowest point
+            self.bp.preorder(pos+1)-1
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        // size of RMQ in bits (parentheses, their rank and min tree, the select samples and the sparse table)
+        self.bp.len() + self.bp.overhead() + self.opens.size() + self.sparse.iter().map(|level| level.size()).sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    fn naive_query(values: &[usize], i: usize, j: usize) -> usize {
+        // leftmost minimum in [i, j]
+        (i..=j).min_by_key(|&k| values[k]).unwrap()
+    }
+
+    #[test]
+    fn matches_naive() {
+        let mut rng: StdRng = StdRng::seed_from_u64(36);
+        // few distinct values give many ties, sizes cross the 512 bit blocks of the parentheses
+        for (n, max_value) in [(1, 1), (2, 1), (7, 3), (300, 4), (3000, 10), (3000, 1_000_000)] {
+            let values: Vec<usize> = (0..n).map(|_| rng.gen_range(0..max_value)).collect();
+            let rmq: Rmq = Rmq::new(&values);
+            for _ in 0..2000 {
+                let i: usize = rng.gen_range(0..n);
+                let j: usize = rng.gen_range(i..n);
+                assert_eq!(rmq.query(i, j), naive_query(&values, i, j), "[{}, {}] of {}", i, j, n);
+            }
+        }
+    }
+
+    #[test]
+    fn sorted_input() {
+        // increasing values nest every parenthesis, decreasing ones close them all right away
+        let increasing: Vec<usize> = (0..2000).collect();
+        let decreasing: Vec<usize> = (0..2000).rev().collect();
+        let (up, down): (Rmq, Rmq) = (Rmq::new(&increasing), Rmq::new(&decreasing));
+        for (i, j) in [(0, 1999), (5, 1500), (600, 601), (1023, 1024)] {
+            assert_eq!(up.query(i, j), i);
+            assert_eq!(down.query(i, j), j);
+        }
+    }
+}
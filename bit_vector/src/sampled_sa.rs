@@ -0,0 +1,62 @@
+// Implement a sampled suffix array: keep suffix array values that are multiples of the sample rate
+// and mark their bwt rows in a bit-vector whose rank1 maps a row to its sample slot
+// methods: get: suffix array value of a row if it was sampled
+            // overhead: size of marks, rank data structure and samples in bits
+            // save/load: save/load sampled suffix array to/from file (Persist)
+
+use bitvec::prelude::*;
+use crate::{FromBits, SpaceUsage, BitAccess};
+use serde::{Serialize,Deserialize};
+
+use crate::rank::RankSupport;
+use crate::int_vector::IntVector;
+
+#[derive(Serialize,Deserialize, Debug)]
+pub struct SampledSuffixArray {
+    marked: RankSupport, // rows whose suffix array value is a multiple of sample_rate
+    samples: IntVector, // sampled suffix array values divided by sample_rate, in row order
+    sample_rate: usize
+}
+
+impl SampledSuffixArray {
+    pub fn new(sa: &[usize], sample_rate: usize) -> SampledSuffixArray {
+        // sample every sample_rate-th text position, so locate needs at most sample_rate-1 lf steps
+        if sample_rate == 0 {
+            panic!("sample rate must be at least 1");
+        }
+        let marks: BitVec = sa.iter().map(|&pos| pos.is_multiple_of(sample_rate)).collect();
+        let samples: Vec<usize> = sa.iter().filter(|&&pos| pos.is_multiple_of(sample_rate)).map(|&pos| pos/sample_rate).collect();
+        SampledSuffixArray {marked: FromBits::from_bits(marks), samples: IntVector::from_slice(&samples), sample_rate}
+    }
+
+    pub fn get(&self, row: usize) -> Option<usize> {
+        // suffix array value at row if the row is sampled
+        if self.marked.get(row) {
+            Some(self.samples.get(self.marked.rank1(row))*self.sample_rate)
+        } else {
+            None
+        }
+    }
+
+    pub fn sample_rate(&self) -> usize {
+        self.sample_rate
+    }
+
+    pub fn len(&self) -> usize {
+        // number of suffix array rows covered
+        self.marked.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn num_samples(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn overhead(&self) -> usize {
+        // size in bits of the row marks, their rank data structure and the samples
+        self.marked.len() + self.marked.overhead() + self.samples.size()
+    }
+}
@@ -1,17 +1,194 @@
  // Implement a succinct, (at most) log time bit-vector select operation (uses rank data structure)
-use std::io;
+// ones/zeros/ones_in/zeros_in: iterate over the positions of the 1s or 0s (both directions) by scanning words
+// succ1/pred1/succ0/pred0: next 1 (0) at or after i / previous 1 (0) before i
+// &a & &b, |, ^, !, difference: bitwise set operations (on the underlying RankSupport), giving a new SelectSupport
+// SelectSamples: constant time select1 over a static bit-vector from sampled positions of its 1s
+use std::ops::{Range, BitAnd, BitOr, BitXor, Not};
 use bitvec::prelude::*;
 use serde::{Serialize,Deserialize};
 use num_integer::div_floor;
 
-use bit_vector::BitVectorTools;
-use crate::rank::RankSupport;
+use crate::{FromBits, SpaceUsage, SpaceNode, BitAccess, Rank, Select};
+use crate::rank::{RankSupport, run_batch};
+use crate::int_vector::IntVector;
 
 
 
 #[derive(Serialize,Deserialize, Debug)]
 pub struct SelectSupport {
-    pub rank_support: RankSupport
+    rank_support: RankSupport // read through Rank/BitAccess (or rank_support, as_bitvec)
+}
+
+const WORD_BITS: usize = usize::BITS as usize;
+// successor/predecessor queries scan this many bits before paying for rank+select
+const NEAR_BITS: usize = 2*WORD_BITS;
+
+// positions of the 1s (or 0s) in [front, back), found a storage word at a time with trailing/leading zero counts
+pub struct BitPositions<'a> {
+    words: &'a [usize],
+    head: usize, // offset of bit 0 in the first word
+    front: usize,
+    back: usize,
+    zeros: bool // look for 0s instead of 1s
+}
+
+impl<'a> BitPositions<'a> {
+    fn new(bit_v: &'a BitVec, range: Range<usize>, zeros: bool) -> BitPositions<'a> {
+        if range.start > range.end || range.end > bit_v.len() {
+            panic!("invalid position entry");
+        }
+        let head: usize = bit_v.as_bitptr().bit().into_inner() as usize;
+        BitPositions {words: bit_v.as_raw_slice(), head, front: range.start, back: range.end, zeros}
+    }
+
+    fn word(&self, w: usize) -> usize {
+        // storage word w with the bits outside [front, back) cleared
+        let mut word: usize = if self.zeros { !self.words[w] } else { self.words[w] };
+        let (front, back): (usize, usize) = (self.front+self.head, self.back+self.head);
+        if front/WORD_BITS == w {
+            word &= usize::MAX << (front%WORD_BITS);
+        }
+        if (back-1)/WORD_BITS == w {
+            word &= usize::MAX >> (WORD_BITS-1-(back-1)%WORD_BITS);
+        }
+        word
+    }
+}
+
+impl Iterator for BitPositions<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        while self.front < self.back {
+            let w: usize = (self.front+self.head)/WORD_BITS;
+            let word: usize = self.word(w);
+            if word != 0 {
+                let pos: usize = w*WORD_BITS + word.trailing_zeros() as usize - self.head;
+                self.front = pos+1;
+                return Some(pos);
+            }
+            self.front = ((w+1)*WORD_BITS-self.head).min(self.back);
+        }
+        None
+    }
+}
+
+impl DoubleEndedIterator for BitPositions<'_> {
+    fn next_back(&mut self) -> Option<usize> {
+        while self.front < self.back {
+            let w: usize = (self.back-1+self.head)/WORD_BITS;
+            let word: usize = self.word(w);
+            if word != 0 {
+                let pos: usize = w*WORD_BITS + (WORD_BITS-1-word.leading_zeros() as usize) - self.head;
+                self.back = pos;
+                return Some(pos);
+            }
+            self.back = (w*WORD_BITS).saturating_sub(self.head).max(self.front);
+        }
+        None
+    }
+}
+
+// Clark style select samples: superblocks of SUPER_ONES 1s spanning at least LONG_SUPER bits keep every position,
+// the others keep the offset of every SUB_ONES-th 1, and subblocks spanning at least LONG_SUB bits keep every offset.
+// Anything else is found by scanning fewer than LONG_SUB bits, so select1 does a bounded amount of work
+const SUPER_ONES: usize = 4096;
+const SUB_ONES: usize = 64;
+const LONG_SUPER: usize = 1 << 20;
+const LONG_SUB: usize = 4096;
+
+// the samples don't own the bits, every query is given the BitVec they were built from
+#[derive(Serialize,Deserialize, Debug)]
+pub struct SelectSamples {
+    ones: usize,
+    super_pos: IntVector, // position of the first 1 of each superblock
+    super_long: RankSupport, // which superblocks are long (rank1 = their index among long superblocks)
+    long_pos: IntVector, // every position in long superblocks
+    sub_pos: IntVector, // offset of the first 1 of each subblock of short superblocks, from the superblock start
+    sub_long: RankSupport, // which of those subblocks are long
+    sub_long_pos: IntVector // every offset in long subblocks, from the superblock start
+}
+
+impl SelectSamples {
+    pub fn new(bit_v: &BitVec) -> SelectSamples {
+        let pos_width: usize = IntVector::width_for(bit_v.len());
+        let offset_width: usize = IntVector::width_for(LONG_SUPER-1);
+        let mut samples: SelectSamples = SelectSamples {
+            ones: 0,
+            super_pos: IntVector::new(pos_width),
+            super_long: RankSupport::from_bits(BitVec::new()),
+            long_pos: IntVector::new(pos_width),
+            sub_pos: IntVector::new(offset_width),
+            sub_long: RankSupport::from_bits(BitVec::new()),
+            sub_long_pos: IntVector::new(offset_width)
+        };
+        // flags are collected first, the rank structures over them are built at the end
+        let mut super_long: BitVec = BitVec::new();
+        let mut sub_long: BitVec = BitVec::new();
+        // one superblock's positions at a time
+        let mut block: Vec<usize> = Vec::with_capacity(SUPER_ONES);
+        let mut ones = bit_v.iter_ones().peekable();
+        while ones.peek().is_some() {
+            block.clear();
+            block.extend(ones.by_ref().take(SUPER_ONES));
+            samples.ones += block.len();
+            let first: usize = block[0];
+            samples.super_pos.push(first);
+            let long: bool = block[block.len()-1]-first+1 >= LONG_SUPER;
+            super_long.push(long);
+            if long {
+                for &pos in block.iter() {
+                    samples.long_pos.push(pos);
+                }
+                continue;
+            }
+            for sub in block.chunks(SUB_ONES) {
+                samples.sub_pos.push(sub[0]-first);
+                let long: bool = sub[sub.len()-1]-sub[0]+1 >= LONG_SUB;
+                sub_long.push(long);
+                if long {
+                    for &pos in sub.iter() {
+                        samples.sub_long_pos.push(pos-first);
+                    }
+                }
+            }
+        }
+        samples.super_long = RankSupport::from_bits(super_long);
+        samples.sub_long = RankSupport::from_bits(sub_long);
+        samples
+    }
+
+    pub fn select1(&self, bit_v: &BitVec, j: usize) -> usize {
+        // same answer as SelectSupport::select1 (one past the jth 1) for the bits the samples were built from
+        if j == 0 {
+            return 0;
+        }
+        if j > self.ones {
+            panic!("Select query is too large and does not exist");
+        }
+        let (block, r): (usize, usize) = ((j-1)/SUPER_ONES, (j-1)%SUPER_ONES);
+        let num_long: usize = self.super_long.rank1(block);
+        if self.super_long.get(block) {
+            return self.long_pos.get(num_long*SUPER_ONES+r)+1;
+        }
+        let first: usize = self.super_pos.get(block);
+        // subblocks are numbered across short superblocks, each of which has SUPER_ONES/SUB_ONES of them (but the last)
+        let sub: usize = (block-num_long)*(SUPER_ONES/SUB_ONES) + r/SUB_ONES;
+        let t: usize = r%SUB_ONES;
+        if self.sub_long.get(sub) {
+            return first+self.sub_long_pos.get(self.sub_long.rank1(sub)*SUB_ONES+t)+1;
+        }
+        // short subblock: its (t+1)th 1 is less than LONG_SUB bits after its first
+        let start: usize = first+self.sub_pos.get(sub);
+        let end: usize = (start+LONG_SUB).min(bit_v.len());
+        start+bit_v[start..end].iter_ones().nth(t).unwrap()+1
+    }
+
+    pub fn size(&self) -> usize {
+        // size of the samples in bits (not counting the bits they sample)
+        self.super_pos.size() + self.super_long.size() + self.long_pos.size()
+            + self.sub_pos.size() + self.sub_long.size() + self.sub_long_pos.size()
+    }
 }
 
 impl SelectSupport{    
@@ -19,50 +196,377 @@ impl SelectSupport{
         // Rank support needs to already be loaded
         // returns position of the first index,j,  for which rank1(j) = i
         // binary search across rank structure to find select
-        let mut L: usize = 0;
-        let mut R: usize = self.rank_support.bit_v.len()-1;
-        
-        let mut rank:usize;
-        let mut m: usize;
-        while L < R{
-            // get new splice
-            m = div_floor(L+R,2);
-            //get new rank
-            rank = self.rank_support.rank1(m);
-            if rank < j {
+        let mut left: usize = 0;
+        let mut right: usize = self.rank_support.len();
+        while left < right {
+            let m: usize = div_floor(left+right, 2);
+            if self.rank_support.rank1(m) < j {
                 //select is in 2nd half
-                L = m+1;
+                left = m+1;
             } else {
                 // select is in 1st half
-                R = m;
-            }     
+                right = m;
+            }
         }
         //check last eq
-        if self.rank_support.rank1(L) == j{
-            m=L;
-            return m;
+        if self.rank_support.rank1(left) == j {
+            left
+        } else {
+            panic!("Select query is too large and does not exist");
+        }
+    }
+
+    pub fn rank_support(&self) -> &RankSupport {
+        // the rank structure the binary search runs over
+        &self.rank_support
+    }
+
+    pub fn as_bitvec(&self) -> &BitVec {
+        self.rank_support.as_bitvec()
+    }
+
+    pub fn select1_batch(&self, queries: &[usize], results: &mut [usize]) {
+        // results[k] = select1(queries[k]), answered in sorted order like RankSupport::rank1_batch
+        self.select1_batch_threads(queries, results, 1);
+    }
+
+    pub fn select1_batch_threads(&self, queries: &[usize], results: &mut [usize], threads: usize) {
+        run_batch(queries, results, threads, |j| self.select1(j));
+    }
+
+    pub fn ones(&self) -> BitPositions<'_> {
+        // positions of every 1 in order (reverse with .rev())
+        self.ones_in(0..self.rank_support.len())
+    }
+
+    pub fn zeros(&self) -> BitPositions<'_> {
+        self.zeros_in(0..self.rank_support.len())
+    }
+
+    pub fn ones_in(&self, range: Range<usize>) -> BitPositions<'_> {
+        // positions of the 1s in range
+        BitPositions::new(self.rank_support.as_bitvec(), range, false)
+    }
+
+    pub fn zeros_in(&self, range: Range<usize>) -> BitPositions<'_> {
+        BitPositions::new(self.rank_support.as_bitvec(), range, true)
+    }
+
+    pub fn difference(&self, other: &SelectSupport) -> SelectSupport {
+        // bits set here but not in other
+        SelectSupport {rank_support: self.rank_support.difference(&other.rank_support)}
+    }
+
+    pub fn succ1(&self, i: usize) -> Option<usize> {
+        // first 1 at or after position i: scan the next couple of words, then fall back to rank+select
+        let len: usize = self.rank_support.len();
+        if i >= len {
+            return None;
+        }
+        let near: Option<usize> = self.ones_in(i..(i+NEAR_BITS).min(len)).next();
+        if near.is_some() || i+NEAR_BITS >= len {
+            return near;
+        }
+        let rank: usize = self.rank_support.rank1(i);
+        if rank == self.rank_support.rank1(len) {
+            None
+        } else {
+            Some(self.select1(rank+1)-1)
+        }
+    }
+
+    pub fn pred1(&self, i: usize) -> Option<usize> {
+        // last 1 before position i
+        let i: usize = i.min(self.rank_support.len());
+        let near: Option<usize> = self.ones_in(i.saturating_sub(NEAR_BITS)..i).next_back();
+        if near.is_some() || i <= NEAR_BITS {
+            return near;
+        }
+        let rank: usize = self.rank_support.rank1(i);
+        if rank == 0 {
+            None
+        } else {
+            Some(self.select1(rank)-1)
+        }
+    }
+
+    pub fn succ0(&self, i: usize) -> Option<usize> {
+        // first 0 at or after position i
+        let len: usize = self.rank_support.len();
+        if i >= len {
+            return None;
+        }
+        let near: Option<usize> = self.zeros_in(i..(i+NEAR_BITS).min(len)).next();
+        if near.is_some() || i+NEAR_BITS >= len {
+            return near;
+        }
+        let rank: usize = self.rank_support.rank0(i);
+        if rank == self.rank_support.rank0(len) {
+            None
+        } else {
+            Some(self.select0(rank+1)-1)
+        }
+    }
+
+    pub fn pred0(&self, i: usize) -> Option<usize> {
+        // last 0 before position i
+        let i: usize = i.min(self.rank_support.len());
+        let near: Option<usize> = self.zeros_in(i.saturating_sub(NEAR_BITS)..i).next_back();
+        if near.is_some() || i <= NEAR_BITS {
+            return near;
+        }
+        let rank: usize = self.rank_support.rank0(i);
+        if rank == 0 {
+            None
+        } else {
+            Some(self.select0(rank)-1)
+        }
+    }
+
+    pub fn select0(&self, j: usize) -> usize {
+        // returns position of the first index, j, for which rank0(j) = i
+        // same binary search as select1, but over the zeros
+        let mut left: usize = 0;
+        let mut right: usize = self.rank_support.len();
+        while left < right {
+            let m: usize = div_floor(left+right, 2);
+            if self.rank_support.rank0(m) < j {
+                left = m+1;
+            } else {
+                right = m;
+            }
+        }
+        if self.rank_support.rank0(left) == j {
+            left
         } else {
             panic!("Select query is too large and does not exist");
         }
     }
 }
 
-impl BitVectorTools for SelectSupport{
-    fn new(bit_v: BitVec) -> Self {
-        SelectSupport {rank_support: BitVectorTools::new(bit_v)}
+impl BitAccess for SelectSupport {
+    fn len(&self) -> usize {
+        self.rank_support.len()
+    }
+
+    fn get(&self, i: usize) -> bool {
+        self.rank_support.get(i)
+    }
+}
+
+impl Rank for SelectSupport {
+    fn rank1(&self, i: usize) -> usize {
+        self.rank_support.rank1(i)
+    }
+}
+
+impl Select for SelectSupport {
+    fn select1(&self, j: usize) -> usize {
+        SelectSupport::select1(self, j)
+    }
+
+    fn select0(&self, j: usize) -> usize {
+        SelectSupport::select0(self, j)
+    }
+
+    fn succ1(&self, i: usize) -> Option<usize> {
+        // word scan for nearby hits
+        SelectSupport::succ1(self, i)
+    }
+
+    fn pred1(&self, i: usize) -> Option<usize> {
+        SelectSupport::pred1(self, i)
+    }
+}
+
+impl BitAnd for &SelectSupport {
+    type Output = SelectSupport;
+
+    fn bitand(self, other: &SelectSupport) -> SelectSupport {
+        SelectSupport {rank_support: &self.rank_support & &other.rank_support}
+    }
+}
+
+impl BitOr for &SelectSupport {
+    type Output = SelectSupport;
+
+    fn bitor(self, other: &SelectSupport) -> SelectSupport {
+        SelectSupport {rank_support: &self.rank_support | &other.rank_support}
+    }
+}
+
+impl BitXor for &SelectSupport {
+    type Output = SelectSupport;
+
+    fn bitxor(self, other: &SelectSupport) -> SelectSupport {
+        SelectSupport {rank_support: &self.rank_support ^ &other.rank_support}
+    }
+}
+
+impl Not for &SelectSupport {
+    type Output = SelectSupport;
+
+    fn not(self) -> SelectSupport {
+        SelectSupport {rank_support: !&self.rank_support}
     }
+}
 
-    fn load(fname: &str) -> Self{
-        // load bitvector and rank data structure from file name
-        SelectSupport {rank_support: BitVectorTools::load(fname)}
+impl FromBits for SelectSupport {
+    fn from_bits(bit_v: BitVec) -> Self {
+        SelectSupport {rank_support: RankSupport::from_bits(bit_v)}
+    }
+}
+
+impl SpaceUsage for SelectSupport {
+    fn payload(&self) -> usize {
+        self.rank_support.payload()
     }
 
     fn overhead(&self) -> usize {
-        return self.rank_support.overhead()
+        // conveniently same as the rank data structure
+        self.rank_support.overhead()
     }
-    
-    fn save(&self, fname: &str) -> Result<(), io::Error>{
-        self.rank_support.save(fname)
+
+    fn space_breakdown(&self) -> SpaceNode {
+        // the rank support's parts, select keeps no samples of its own (binary search over rank)
+        let rank: SpaceNode = self.rank_support.space_breakdown();
+        let part = |name: &str| rank.get(name).cloned().unwrap_or_else(|| SpaceNode::leaf(name, 0));
+        SpaceNode::branch("select support", vec![
+            part("payload"),
+            part("rank tables"),
+            SpaceNode::leaf("select samples", 0),
+            part("metadata")
+        ])
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
 
-}
\ No newline at end of file
+    #[test]
+    fn samples_match_select_support() {
+        let mut rng: StdRng = StdRng::seed_from_u64(36);
+        // dense random bits, then 1s 300 bits apart (a long superblock), then 1s 100 bits apart (long subblocks)
+        let mut bit_v: BitVec = (0..100_000).map(|_| rng.gen_bool(0.5)).collect();
+        for spacing in [300, 100] {
+            for _ in 0..2*SUPER_ONES {
+                bit_v.extend(std::iter::repeat_n(false, spacing-1));
+                bit_v.push(true);
+            }
+        }
+        bit_v.extend((0..10_000).map(|_| rng.gen_bool(0.01)));
+        let samples: SelectSamples = SelectSamples::new(&bit_v);
+        assert!(!samples.long_pos.is_empty() && !samples.sub_long_pos.is_empty());
+        let positions: Vec<usize> = bit_v.iter_ones().collect();
+        assert_eq!(samples.select1(&bit_v, 0), 0);
+        for (k, &pos) in positions.iter().enumerate() {
+            assert_eq!(samples.select1(&bit_v, k+1), pos+1);
+        }
+        // and agrees with the binary search
+        let select_support: SelectSupport = SelectSupport::from_bits(bit_v.clone());
+        for j in (1..=positions.len()).step_by(997) {
+            assert_eq!(samples.select1(&bit_v, j), select_support.select1(j));
+        }
+    }
+
+    #[test]
+    fn space_breakdown_adds_up() {
+        let select_support: SelectSupport = SelectSupport::from_bits(BitVec::repeat(true, 10_000));
+        let space: SpaceNode = select_support.space_breakdown();
+        let rank: SpaceNode = select_support.rank_support.space_breakdown();
+        assert_eq!(space.bits, rank.bits);
+        assert_eq!(space.bits_of("payload"), 10_000);
+        assert_eq!(space.bits_of("rank tables"), select_support.overhead());
+        assert_eq!(space.get("select samples").map(|node| node.bits), Some(0));
+        assert_eq!(space.bits_of("metadata"), rank.bits_of("metadata"));
+    }
+
+    #[test]
+    fn samples_of_empty_bits() {
+        let bit_v: BitVec = BitVec::new();
+        assert_eq!(SelectSamples::new(&bit_v).select1(&bit_v, 0), 0);
+    }
+
+    fn unaligned_bits(rng: &mut StdRng, len: usize, density: f64) -> BitVec {
+        // copied out of a longer vector 3 bits in, so bit 0 is not the first bit of its storage word
+        let big: BitVec = (0..len+3).map(|_| rng.gen_bool(density)).collect();
+        let bit_v: BitVec = big[3..].to_bitvec();
+        assert_eq!(bit_v.as_bitptr().bit().into_inner(), 3);
+        bit_v
+    }
+
+    fn positions_of(bits: &[bool], range: Range<usize>, value: bool) -> Vec<usize> {
+        range.filter(|&i| bits[i] == value).collect()
+    }
+
+    #[test]
+    fn bit_positions_match_vec() {
+        let mut rng: StdRng = StdRng::seed_from_u64(44);
+        for len in [0, 1, 61, 63, 64, 65, 127, 128, 129, 300] {
+            for density in [0.0, 0.05, 0.5, 0.95, 1.0] {
+                let bit_v: BitVec = unaligned_bits(&mut rng, len, density);
+                let bits: Vec<bool> = bit_v.iter().by_vals().collect();
+                let select_support: SelectSupport = SelectSupport::from_bits(bit_v);
+                let ones: Vec<usize> = positions_of(&bits, 0..len, true);
+                let zeros: Vec<usize> = positions_of(&bits, 0..len, false);
+                assert_eq!(select_support.ones().collect::<Vec<usize>>(), ones, "ones of {} at {}", len, density);
+                assert_eq!(select_support.zeros().collect::<Vec<usize>>(), zeros, "zeros of {} at {}", len, density);
+                assert_eq!(select_support.ones().rev().collect::<Vec<usize>>(), ones.iter().rev().cloned().collect::<Vec<usize>>());
+                assert_eq!(select_support.zeros().rev().collect::<Vec<usize>>(), zeros.iter().rev().cloned().collect::<Vec<usize>>());
+                for _ in 0..50 {
+                    let a: usize = rng.gen_range(0..=len);
+                    let b: usize = rng.gen_range(a..=len);
+                    for value in [true, false] {
+                        let expected: Vec<usize> = positions_of(&bits, a..b, value);
+                        let range = || if value { select_support.ones_in(a..b) } else { select_support.zeros_in(a..b) };
+                        assert_eq!(range().collect::<Vec<usize>>(), expected, "{}..{} of {}", a, b, len);
+                        // take from both ends at random until they meet, nothing is returned twice or skipped
+                        let mut positions: BitPositions = range();
+                        let (mut front, mut back): (Vec<usize>, Vec<usize>) = (Vec::new(), Vec::new());
+                        loop {
+                            let next: Option<usize> = if rng.gen_bool(0.5) {
+                                positions.next().inspect(|&pos| front.push(pos))
+                            } else {
+                                positions.next_back().inspect(|&pos| back.push(pos))
+                            };
+                            if next.is_none() {
+                                break;
+                            }
+                        }
+                        assert_eq!((positions.next(), positions.next_back()), (None, None));
+                        front.extend(back.iter().rev());
+                        assert_eq!(front, expected, "both ends of {}..{} of {}", a, b, len);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn succ_and_pred_match_vec() {
+        let mut rng: StdRng = StdRng::seed_from_u64(45);
+        // sparse and dense bits leave gaps longer than NEAR_BITS, which fall back to rank+select
+        for len in [0, 1, 64, 65, 127, 128, 129, 2000] {
+            for density in [0.0, 0.002, 0.3, 0.998, 1.0] {
+                let bit_v: BitVec = unaligned_bits(&mut rng, len, density);
+                let bits: Vec<bool> = bit_v.iter().by_vals().collect();
+                let select_support: SelectSupport = SelectSupport::from_bits(bit_v);
+                for i in 0..=len+1 {
+                    for value in [true, false] {
+                        let succ: Option<usize> = (i..len).find(|&k| bits[k] == value);
+                        let pred: Option<usize> = (0..i.min(len)).rev().find(|&k| bits[k] == value);
+                        let (got_succ, got_pred) = if value {
+                            (select_support.succ1(i), select_support.pred1(i))
+                        } else {
+                            (select_support.succ0(i), select_support.pred0(i))
+                        };
+                        assert_eq!(got_succ, succ, "succ{}({}) of {} at {}", value as u8, i, len, density);
+                        assert_eq!(got_pred, pred, "pred{}({}) of {} at {}", value as u8, i, len, density);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,24 +1,22 @@
-use std::io;
-use std::io::{BufWriter, BufReader, Write};
-use std::fs::File;
-
 use bitvec::prelude::*;
-use bit_vector::BitVectorTools;
-use get_size::GetSize;
+use crate::{FromBits, Select, SpaceUsage, SpaceNode};
 use serde::{Serialize,Deserialize};
 
 use crate::select::SelectSupport;
+use crate::string_store::StringStore;
 
 #[derive(Debug)]
 pub struct SparseArrayBuilder{
     bit_v:BitVec,
-    values:Vec<String>
+    values:String, // appended values back to back
+    ends:Vec<usize> // where each appended value stops in values
 }
 
+// S is the bit-vector marking present positions, any Select implementation (SelectSupport by default)
 #[derive(Serialize,Deserialize, Debug)]
-pub struct SparseArray{
-    values: Vec<String>,
-    select_support: SelectSupport //which includes bit_v
+pub struct SparseArray<S = SelectSupport>{
+    values: StringStore,
+    select_support: S //which includes bit_v
 }
 
 impl SparseArrayBuilder{
@@ -26,85 +24,216 @@ impl SparseArrayBuilder{
     pub fn create(size: usize) -> SparseArrayBuilder {
         // let mut array: SparseArrayBuilder = SparseArrayBuilder {bit_v: BitVec::with_capacity(size), values: Vec::new()}; 
         // weird error is occuring where bit_v has extra ones. so fixing by initiating it with 0 value bitvec
-        let mut array: SparseArrayBuilder = SparseArrayBuilder {bit_v: bitvec![0;size], values: Vec::new()};
+        let mut array: SparseArrayBuilder = SparseArrayBuilder {bit_v: bitvec![0;size], values: String::new(), ends: Vec::new()};
         unsafe{array.bit_v.set_len(size)};
-        return array;
+        array
     }
 
     pub fn append(&mut self, elem: String, pos: usize) {
+        self.append_str(&elem, pos);
+    }
+
+    fn append_str(&mut self, elem: &str, pos: usize) {
+        // copy elem straight into the values buffer
         if pos > self.bit_v.capacity(){
             panic!("invalid position entry")
         }
         self.bit_v.set(pos, true);
-        self.values.push(elem);
+        self.values.push_str(elem);
+        self.ends.push(self.values.len());
     }
 
     pub fn finalize(self) -> SparseArray{
         //create rank data structure (which will also be used for select)
-        SparseArray {values: self.values, select_support: BitVectorTools::new(self.bit_v.clone())}
+        self.finalize_with()
+    }
+
+    pub fn finalize_with<S: Select + FromBits>(self) -> SparseArray<S> {
+        // same as finalize, with another bit-vector type marking the present positions
+        SparseArray {values: StringStore::new(self.values, &self.ends), select_support: S::from_bits(self.bit_v)}
     }
 }
 
-impl SparseArray{
+impl<S: Select + SpaceUsage> SparseArray<S>{
     //use sparse array
-    pub fn get_at_rank(&self, r:usize, elem: &mut String) -> bool{
-        // look at rth item; if there are at least r items, return true
-        if self.values.len() >= r {
-            *elem = (self.values[r]).to_string();
-            return true;
+    pub fn get_at_rank(&self, r:usize) -> Option<&str>{
+        // look at rth item; if there are more than r items, return it
+        if r < self.values.len() {
+            Some(self.values.get(r))
         } else{
-            return false;
+            None
         }
     }
     
-    pub fn get_at_index(&self, r:usize, elem: &mut String) -> bool{
-          // if bit at index r is 1, return true and set value to elem
-          let result: bool = self.select_support.rank_support.bit_v[r];
-          if result {
-            *elem = (self.values[self.select_support.rank_support.rank1(r)]).to_string();
+    pub fn get_at_index(&self, r:usize) -> Option<&str>{
+          // if bit at index r is 1, return the value stored there
+          if self.select_support.get(r) {
+            Some(self.values.get(self.select_support.rank1(r)))
+          } else {
+            None
           }
-          return result
     }
 
     pub fn get_index_of(&self, r:usize) -> Option<usize> {
         //take rank r and return index in sparse array where rth present element appears
         if r > self.values.len(){ // r is impossibly big
-            return None;
+            None
         } else{
-            return Some((self.select_support.select1(r) as i64 -1)as usize);
+            Some((self.select_support.select1(r) as i64 -1)as usize)
         }
     } 
 
+    pub fn next_present(&self, i: usize) -> Option<usize> {
+        // index of the first present element at or after index i
+        self.select_support.succ1(i)
+    }
+
+    pub fn prev_present(&self, i: usize) -> Option<usize> {
+        // index of the last present element before index i
+        self.select_support.pred1(i)
+    }
+
+    fn present(&self) -> impl Iterator<Item = usize> + '_ {
+        // present positions in order, each found from the last with succ1
+        std::iter::successors(self.select_support.succ1(0), |&pos| self.select_support.succ1(pos+1))
+    }
+
+    fn merge<F: Fn(bool, bool) -> bool>(&self, other: &SparseArray<S>, keep: F) -> SparseArray<S> where S: FromBits {
+        // walk both lists of present positions in order (no dense and/or of the bit-vectors),
+        // keeping a position if keep(in self, in other); its value comes from self if present there
+        let size: usize = self.select_support.len();
+        if size != other.select_support.len() {
+            panic!("sparse arrays must have the same size");
+        }
+        let mut array: SparseArrayBuilder = SparseArrayBuilder::create(size);
+        let mut mine = self.present().enumerate().peekable();
+        let mut theirs = other.present().enumerate().peekable();
+        loop {
+            let pos: usize = match (mine.peek(), theirs.peek()) {
+                (Some(&(_, a)), Some(&(_, b))) => a.min(b),
+                (Some(&(_, a)), None) => a,
+                (None, Some(&(_, b))) => b,
+                (None, None) => break
+            };
+            let in_self: Option<usize> = mine.next_if(|&(_, a)| a == pos).map(|(rank, _)| rank);
+            let in_other: Option<usize> = theirs.next_if(|&(_, b)| b == pos).map(|(rank, _)| rank);
+            if keep(in_self.is_some(), in_other.is_some()) {
+                let value: &str = match in_self {
+                    Some(rank) => self.values.get(rank),
+                    None => other.values.get(in_other.unwrap())
+                };
+                array.append_str(value, pos);
+            }
+        }
+        array.finalize_with()
+    }
+
+    pub fn and(&self, other: &SparseArray<S>) -> SparseArray<S> where S: FromBits {
+        // positions present in both (values from self)
+        self.merge(other, |a, b| a && b)
+    }
+
+    pub fn or(&self, other: &SparseArray<S>) -> SparseArray<S> where S: FromBits {
+        // positions present in either (values from self where present there)
+        self.merge(other, |a, b| a || b)
+    }
+
+    pub fn xor(&self, other: &SparseArray<S>) -> SparseArray<S> where S: FromBits {
+        // positions present in exactly one
+        self.merge(other, |a, b| a != b)
+    }
+
+    pub fn difference(&self, other: &SparseArray<S>) -> SparseArray<S> where S: FromBits {
+        // positions present here but not in other
+        self.merge(other, |a, b| a && !b)
+    }
+
     pub fn num_elem_at(&self, r:usize) -> usize {
         // inclusive rank of bitvector (count of 1s up to and including r)
-        return self.select_support.rank_support.rank1(r+1);
+        self.select_support.rank1(r+1)
     }
 
     pub fn size(&self) -> usize {
         // get size of sparse array in bits (value vector, and extra (rank) data structure)
-        return self.select_support.overhead()+ self.values.get_heap_size()*8;
+        self.select_support.overhead()+ self.values.size()
     }
 
     pub fn num_elem(&self) -> usize{
         // return number of present elements
-        return self.values.len();
+        self.values.len()
     }
+}
 
-    pub fn save(&self, fname: &str) -> Result<(), io::Error>{
-        //save SparseArray (value, select support to file)
-        let file: File = File::create(fname)?;
-        let mut writer: BufWriter<File> = BufWriter::new(file);
-        serde_json::to_writer(&mut writer, &self)?;
-        writer.flush()?;
-        Ok(())
+impl<S: Select + SpaceUsage> SpaceUsage for SparseArray<S> {
+    fn payload(&self) -> usize {
+        // the stored values
+        self.values.size()
     }
 
-    pub fn load(fname: &str) -> SparseArray {
-        // load SparseArray file name
-        let readfile = File::open(fname).unwrap();
-        let reader: BufReader<File> = BufReader::new(readfile);
-        let array: SparseArray = serde_json::from_reader(reader).unwrap();
-        return array;
+    fn overhead(&self) -> usize {
+        // rank/select structure over the present positions
+        self.select_support.overhead()
+    }
+
+    fn space_breakdown(&self) -> SpaceNode {
+        // the positions bit-vector with its index, the values, and the struct itself
+        // (minus the inline select structure, already counted under positions)
+        let metadata: usize = std::mem::size_of::<SparseArray<S>>() - std::mem::size_of::<S>();
+        SpaceNode::branch("sparse array", vec![
+            self.select_support.space_breakdown().renamed("positions"),
+            self.values.space_breakdown(),
+            SpaceNode::leaf("metadata", metadata*u8::BITS as usize)
+        ])
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    fn random_array(rng: &mut StdRng, size: usize, density: f64, tag: &str) -> (SparseArray, BTreeMap<usize, String>) {
+        let mut array: SparseArrayBuilder = SparseArrayBuilder::create(size);
+        let mut expected: BTreeMap<usize, String> = BTreeMap::new();
+        for pos in 0..size {
+            if rng.gen_bool(density) {
+                let value: String = format!("{}{}", tag, pos);
+                array.append(value.clone(), pos);
+                expected.insert(pos, value);
+            }
+        }
+        (array.finalize(), expected)
+    }
+
+    fn check_array(array: &SparseArray, expected: &BTreeMap<usize, String>, size: usize) {
+        assert_eq!(array.num_elem(), expected.len());
+        for pos in 0..size {
+            assert_eq!(array.get_at_index(pos), expected.get(&pos).map(|value| value.as_str()), "index {}", pos);
+        }
+        for (rank, (&pos, value)) in expected.iter().enumerate() {
+            assert_eq!(array.get_at_rank(rank), Some(value.as_str()));
+            assert_eq!(array.get_index_of(rank+1), Some(pos));
+        }
+    }
+
+    #[test]
+    fn set_operations_match_maps() {
+        let mut rng: StdRng = StdRng::seed_from_u64(47);
+        for (size, density) in [(1, 0.5), (64, 0.3), (129, 0.5), (1000, 0.02), (1000, 0.9)] {
+            let (a, a_values): (SparseArray, BTreeMap<usize, String>) = random_array(&mut rng, size, density, "a");
+            let (b, b_values): (SparseArray, BTreeMap<usize, String>) = random_array(&mut rng, size, density, "b");
+            // values come from a wherever a has the position
+            let merged = |keep: fn(bool, bool) -> bool| -> BTreeMap<usize, String> {
+                (0..size).filter(|pos| keep(a_values.contains_key(pos), b_values.contains_key(pos)))
+                    .map(|pos| (pos, a_values.get(&pos).or(b_values.get(&pos)).unwrap().clone()))
+                    .collect()
+            };
+            check_array(&a.and(&b), &merged(|x, y| x && y), size);
+            check_array(&a.or(&b), &merged(|x, y| x || y), size);
+            check_array(&a.xor(&b), &merged(|x, y| x != y), size);
+            check_array(&a.difference(&b), &merged(|x, y| x && !y), size);
+            check_array(&a.or(&a), &a_values, size);
+        }
+    }
+}
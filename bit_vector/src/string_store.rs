@@ -0,0 +1,65 @@
+// Implement compact storage for a list of strings: one concatenated buffer plus bit-packed offsets
+// methods: get: ith string as a slice of the buffer (no per-string allocation)
+            // size: size of buffer and offsets in bits
+            // space_breakdown: buffer, offsets and their unused capacity in bits
+use serde::{Serialize,Deserialize};
+
+use crate::SpaceNode;
+use crate::int_vector::IntVector;
+
+#[derive(Serialize,Deserialize, Debug)]
+pub struct StringStore {
+    bytes: String, // every string back to back
+    offsets: IntVector // start of each string in bytes, plus the total length at the end
+}
+
+impl StringStore {
+    pub fn new(bytes: String, ends: &[usize]) -> StringStore {
+        // ends[i] is where the ith string stops in bytes (strings are back to back from 0)
+        let mut offsets: Vec<usize> = Vec::with_capacity(ends.len()+1);
+        offsets.push(0);
+        offsets.extend_from_slice(ends);
+        if offsets.windows(2).any(|w| w[0] > w[1]) || offsets[ends.len()] != bytes.len() {
+            panic!("string ends do not cover the buffer");
+        }
+        StringStore {bytes, offsets: IntVector::from_slice(&offsets)}
+    }
+
+    pub fn from_strs<S: AsRef<str>>(strs: &[S]) -> StringStore {
+        let mut bytes: String = String::new();
+        let mut ends: Vec<usize> = Vec::with_capacity(strs.len());
+        for s in strs {
+            bytes.push_str(s.as_ref());
+            ends.push(bytes.len());
+        }
+        StringStore::new(bytes, &ends)
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len()-1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, i: usize) -> &str {
+        // ith string
+        &self.bytes[self.offsets.get(i)..self.offsets.get(i+1)]
+    }
+
+    pub fn size(&self) -> usize {
+        // size in bits of the buffer and the packed offsets
+        self.bytes.len()*u8::BITS as usize + self.offsets.size()
+    }
+
+    pub fn space_breakdown(&self) -> SpaceNode {
+        let byte_bits: usize = u8::BITS as usize;
+        SpaceNode::branch("values", vec![
+            SpaceNode::leaf("bytes", self.bytes.len()*byte_bits),
+            SpaceNode::leaf("offsets", self.offsets.size()),
+            SpaceNode::leaf("unused capacity", (self.bytes.capacity()-self.bytes.len())*byte_bits
+                + self.offsets.allocated_size()-self.offsets.size())
+        ])
+    }
+}
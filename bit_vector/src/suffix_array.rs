@@ -0,0 +1,205 @@
+// Implement linear time suffix array construction (SA-IS) and lcp array construction (Kasai)
+// methods: suffix_array: suffix array of a byte text
+            // suffix_array_int: suffix array of a text over a small integer alphabet [0, alphabet_size)
+            // lcp_array: lcp[i] = longest common prefix of suffixes sa[i-1] and sa[i] (lcp[0] = 0)
+// a shorter suffix sorts before any longer suffix it is a prefix of, so no sentinel is needed
+
+const THRESHOLD_NAIVE: usize = 10;
+
+pub fn suffix_array(text: &[u8]) -> Vec<usize> {
+    // suffix array of a byte text
+    let s: Vec<usize> = text.iter().map(|&c| c as usize).collect();
+    sa_is(&s, u8::MAX as usize)
+}
+
+pub fn suffix_array_int(text: &[usize], alphabet_size: usize) -> Vec<usize> {
+    // suffix array of a text whose symbols are all < alphabet_size
+    if text.iter().any(|&c| c >= alphabet_size) {
+        panic!("symbol outside of alphabet");
+    }
+    if text.is_empty() {
+        return Vec::new();
+    }
+    sa_is(text, alphabet_size-1)
+}
+
+pub fn lcp_array<T: Eq>(text: &[T], sa: &[usize]) -> Vec<usize> {
+    // kasai: going through suffixes in text order, the lcp drops by at most 1 each step
+    let n: usize = text.len();
+    let mut rank: Vec<usize> = vec![0; n];
+    for (i, &pos) in sa.iter().enumerate() {
+        rank[pos] = i;
+    }
+    let mut lcp: Vec<usize> = vec![0; n];
+    let mut h: usize = 0;
+    for i in 0..n {
+        if rank[i] == 0 {
+            h = 0;
+            continue;
+        }
+        let j: usize = sa[rank[i]-1];
+        while i+h < n && j+h < n && text[i+h] == text[j+h] {
+            h += 1;
+        }
+        lcp[rank[i]] = h;
+        h = h.saturating_sub(1);
+    }
+    lcp
+}
+
+fn sa_naive(s: &[usize]) -> Vec<usize> {
+    // sort suffixes directly (used for short texts)
+    let mut sa: Vec<usize> = (0..s.len()).collect();
+    sa.sort_by(|&a, &b| s[a..].cmp(&s[b..]));
+    sa
+}
+
+fn sa_is(s: &[usize], upper: usize) -> Vec<usize> {
+    // s has symbols in [0, upper]
+    let n: usize = s.len();
+    if n < THRESHOLD_NAIVE {
+        return sa_naive(s);
+    }
+    // classify suffixes: ls[i] is true for S-type (smaller than the next suffix)
+    let mut ls: Vec<bool> = vec![false; n];
+    for i in (0..n-1).rev() {
+        ls[i] = if s[i] == s[i+1] { ls[i+1] } else { s[i] < s[i+1] };
+    }
+    // bucket starts for S-type (sum_s) and L-type (sum_l) suffixes of each symbol
+    let mut sum_l: Vec<usize> = vec![0; upper+1];
+    let mut sum_s: Vec<usize> = vec![0; upper+1];
+    for i in 0..n {
+        if !ls[i] {
+            sum_s[s[i]] += 1;
+        } else {
+            sum_l[s[i]+1] += 1;
+        }
+    }
+    for i in 0..=upper {
+        sum_s[i] += sum_l[i];
+        if i < upper {
+            sum_l[i+1] += sum_s[i];
+        }
+    }
+
+    // leftmost S-type positions
+    let mut lms_map: Vec<usize> = vec![usize::MAX; n+1];
+    let mut lms: Vec<usize> = Vec::new();
+    for i in 1..n {
+        if !ls[i-1] && ls[i] {
+            lms_map[i] = lms.len();
+            lms.push(i);
+        }
+    }
+    let m: usize = lms.len();
+
+    let mut sa: Vec<usize> = vec![usize::MAX; n];
+    induce(s, &ls, &sum_s, &sum_l, &lms, &mut sa);
+
+    if m > 0 {
+        // name lms substrings in sorted order, equal substrings share a name
+        let sorted_lms: Vec<usize> = sa.iter().cloned().filter(|&v| lms_map[v] != usize::MAX).collect();
+        let mut rec_s: Vec<usize> = vec![0; m];
+        let mut rec_upper: usize = 0;
+        rec_s[lms_map[sorted_lms[0]]] = 0;
+        for i in 1..m {
+            let mut l: usize = sorted_lms[i-1];
+            let mut r: usize = sorted_lms[i];
+            let end_l: usize = if lms_map[l]+1 < m { lms[lms_map[l]+1] } else { n };
+            let end_r: usize = if lms_map[r]+1 < m { lms[lms_map[r]+1] } else { n };
+            let mut same: bool = true;
+            if end_l-l != end_r-r {
+                same = false;
+            } else {
+                while l < end_l && s[l] == s[r] {
+                    l += 1;
+                    r += 1;
+                }
+                if l == n || s[l] != s[r] {
+                    same = false;
+                }
+            }
+            if !same {
+                rec_upper += 1;
+            }
+            rec_s[lms_map[sorted_lms[i]]] = rec_upper;
+        }
+        // sort the lms suffixes recursively by their names, then induce the full order from them
+        let rec_sa: Vec<usize> = sa_is(&rec_s, rec_upper);
+        let sorted_lms: Vec<usize> = rec_sa.iter().map(|&i| lms[i]).collect();
+        induce(s, &ls, &sum_s, &sum_l, &sorted_lms, &mut sa);
+    }
+    sa
+}
+
+fn induce(s: &[usize], ls: &[bool], sum_s: &[usize], sum_l: &[usize], lms: &[usize], sa: &mut [usize]) {
+    // induced sort: place lms suffixes, then sweep left to right for L-type and right to left for S-type
+    let n: usize = s.len();
+    sa.fill(usize::MAX);
+    let mut buf: Vec<usize> = sum_s.to_vec();
+    for &d in lms {
+        if d == n {
+            continue;
+        }
+        sa[buf[s[d]]] = d;
+        buf[s[d]] += 1;
+    }
+    buf.copy_from_slice(sum_l);
+    sa[buf[s[n-1]]] = n-1;
+    buf[s[n-1]] += 1;
+    for i in 0..n {
+        let v: usize = sa[i];
+        if v != usize::MAX && v >= 1 && !ls[v-1] {
+            sa[buf[s[v-1]]] = v-1;
+            buf[s[v-1]] += 1;
+        }
+    }
+    buf.copy_from_slice(sum_l);
+    for i in (0..n).rev() {
+        let v: usize = sa[i];
+        if v != usize::MAX && v >= 1 && ls[v-1] {
+            buf[s[v-1]+1] -= 1;
+            sa[buf[s[v-1]+1]] = v-1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    fn naive_suffix_array<T: Ord>(text: &[T]) -> Vec<usize> {
+        let mut sa: Vec<usize> = (0..text.len()).collect();
+        sa.sort_by(|&a, &b| text[a..].cmp(&text[b..]));
+        sa
+    }
+
+    #[test]
+    fn matches_naive_sort() {
+        for text in [&b""[..], b"a", b"banana", b"mississippi", b"aaaaaaaaaaaaaaaaaaaa", b"abababababababab"] {
+            assert_eq!(suffix_array(text), naive_suffix_array(text));
+        }
+        // small alphabets give long repeats and deep recursion, large ones few repeats
+        let mut rng: StdRng = StdRng::seed_from_u64(28);
+        for alphabet_size in [1, 2, 4, 26, 1000] {
+            for n in [1, 2, 9, 10, 11, 50, 500, 3000] {
+                let text: Vec<usize> = (0..n).map(|_| rng.gen_range(0..alphabet_size)).collect();
+                assert_eq!(suffix_array_int(&text, alphabet_size), naive_suffix_array(&text));
+            }
+        }
+    }
+
+    #[test]
+    fn lcp_matches_naive() {
+        let mut rng: StdRng = StdRng::seed_from_u64(29);
+        let text: Vec<u8> = (0..2000).map(|_| b"ACGT"[rng.gen_range(0..4)]).collect();
+        let sa: Vec<usize> = suffix_array(&text);
+        let lcp: Vec<usize> = lcp_array(&text, &sa);
+        assert_eq!(lcp[0], 0);
+        for i in 1..sa.len() {
+            let common: usize = text[sa[i-1]..].iter().zip(&text[sa[i]..]).take_while(|(a, b)| a == b).count();
+            assert_eq!(lcp[i], common);
+        }
+    }
+}
@@ -0,0 +1,190 @@
+// Implement a static trie over string keys: LOUDS tree shape, one byte label per node and a bit-vector marking key ends
+// key ids are the rank of a key's end node among all key end nodes (bfs order)
+// methods: lookup: id of a key
+            // key: key with an id
+            // keys_with_prefix: all keys starting with a prefix (sorted)
+            // size: size of trie in bits
+            // save/load: save/load trie to/from file (Persist)
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use bitvec::prelude::*;
+use crate::{FromBits, SpaceUsage, BitAccess, Rank};
+use serde::{Serialize,Deserialize};
+
+use crate::louds::LoudsTree;
+use crate::select::SelectSupport;
+
+#[derive(Serialize,Deserialize, Debug)]
+pub struct Trie {
+    louds: LoudsTree,
+    labels: Vec<u8>, // byte on the edge into each node in bfs order (0 for the root)
+    terminal: SelectSupport // nodes where a key ends
+}
+
+impl Trie {
+    pub fn new<S: AsRef<str>>(keys: &[S]) -> Trie {
+        // build trie level by level, each node is a range of sorted keys sharing a prefix
+        let mut sorted: Vec<&[u8]> = keys.iter().map(|key| key.as_ref().as_bytes()).collect();
+        sorted.sort_unstable();
+        sorted.dedup();
+        let mut louds_bits: BitVec = bitvec![1, 0];
+        let mut labels: Vec<u8> = vec![0];
+        let mut terminal: BitVec = BitVec::new();
+        // (first key, end key, depth) of each node in bfs order
+        let mut queue: VecDeque<(usize, usize, usize)> = VecDeque::from([(0, sorted.len(), 0)]);
+        while let Some((lo, hi, depth)) = queue.pop_front() {
+            let mut start: usize = lo;
+            let ends_here: bool = start < hi && sorted[start].len() == depth;
+            terminal.push(ends_here);
+            if ends_here {
+                start += 1;
+            }
+            // group remaining keys by their next byte
+            while start < hi {
+                let label: u8 = sorted[start][depth];
+                let mut end: usize = start+1;
+                while end < hi && sorted[end][depth] == label {
+                    end += 1;
+                }
+                louds_bits.push(true);
+                labels.push(label);
+                queue.push_back((start, end, depth+1));
+                start = end;
+            }
+            louds_bits.push(false);
+        }
+        Trie {louds: FromBits::from_bits(louds_bits), labels, terminal: FromBits::from_bits(terminal)}
+    }
+
+    pub fn len(&self) -> usize {
+        // number of keys
+        self.terminal.rank1(self.labels.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn find_node(&self, key: &[u8]) -> Option<usize> {
+        // follow key from the root, children labels are sorted so binary search them
+        let mut node: usize = 0;
+        for &byte in key {
+            let children: Range<usize> = self.louds.children(node);
+            let offset: usize = self.labels[children.clone()].binary_search(&byte).ok()?;
+            node = children.start+offset;
+        }
+        Some(node)
+    }
+
+    pub fn lookup(&self, key: &str) -> Option<usize> {
+        // id of key if it is in the trie
+        let node: usize = self.find_node(key.as_bytes())?;
+        if self.terminal.get(node) {
+            Some(self.terminal.rank1(node))
+        } else {
+            None
+        }
+    }
+
+    pub fn key(&self, id: usize) -> Option<String> {
+        // key with this id, read by walking parent links back to the root
+        if id >= self.len() {
+            return None;
+        }
+        let mut node: usize = self.terminal.select1(id+1)-1;
+        let mut bytes: Vec<u8> = Vec::new();
+        while let Some(parent) = self.louds.parent(node) {
+            bytes.push(self.labels[node]);
+            node = parent;
+        }
+        bytes.reverse();
+        Some(String::from_utf8(bytes).unwrap())
+    }
+
+    pub fn keys_with_prefix(&self, prefix: &str) -> Vec<String> {
+        // every key starting with prefix, in sorted order (depth first, children in label order)
+        let mut keys: Vec<String> = Vec::new();
+        let node: usize = match self.find_node(prefix.as_bytes()) {
+            Some(node) => node,
+            None => return keys
+        };
+        let mut stack: Vec<(usize, Vec<u8>)> = vec![(node, prefix.as_bytes().to_vec())];
+        while let Some((node, bytes)) = stack.pop() {
+            for child in self.louds.children(node).rev() {
+                let mut child_bytes: Vec<u8> = bytes.clone();
+                child_bytes.push(self.labels[child]);
+                stack.push((child, child_bytes));
+            }
+            if self.terminal.get(node) {
+                keys.push(String::from_utf8(bytes).unwrap());
+            }
+        }
+        keys
+    }
+
+    pub fn size(&self) -> usize {
+        // size of trie in bits (LOUDS bits, labels, key end marks and their rank structures)
+        let num_nodes: usize = self.labels.len();
+        (2*num_nodes+1) + self.louds.overhead() + num_nodes*u8::BITS as usize
+            + num_nodes + self.terminal.overhead()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    fn check_trie(keys: &[&str]) {
+        let trie: Trie = Trie::new(keys);
+        let expected: BTreeSet<&str> = keys.iter().cloned().collect();
+        assert_eq!(trie.len(), expected.len());
+        let mut ids: Vec<usize> = Vec::new();
+        for &key in &expected {
+            let id: usize = trie.lookup(key).unwrap_or_else(|| panic!("{:?} not found", key));
+            assert_eq!(trie.key(id).as_deref(), Some(key));
+            ids.push(id);
+        }
+        ids.sort_unstable();
+        assert_eq!(ids, (0..expected.len()).collect::<Vec<usize>>());
+        assert_eq!(trie.key(expected.len()), None);
+        // every prefix of a key (on a char boundary), which is only found when it is a key itself
+        for &key in &expected {
+            for (end, _) in key.char_indices().chain([(key.len(), ' ')]) {
+                let prefix: &str = &key[..end];
+                assert_eq!(trie.lookup(prefix).is_some(), expected.contains(prefix), "lookup {:?}", prefix);
+                let with_prefix: Vec<&str> = expected.iter().cloned().filter(|k| k.starts_with(prefix)).collect();
+                assert_eq!(trie.keys_with_prefix(prefix), with_prefix, "keys_with_prefix {:?}", prefix);
+            }
+            assert_eq!(trie.lookup(&format!("{}x", key)).is_some(), expected.contains(format!("{}x", key).as_str()));
+        }
+    }
+
+    #[test]
+    fn keys_that_prefix_other_keys() {
+        check_trie(&["a", "ab", "abc", "abd", "b", "abc", "bcd", "bc"]);
+        check_trie(&["", "x", "xy"]);
+        check_trie(&[""]);
+    }
+
+    #[test]
+    fn multibyte_labels() {
+        // é and ü share their first byte, so a node sits in the middle of a character
+        check_trie(&["é", "ü", "über", "übung", "日本", "日本語", "ab", "a日"]);
+        let trie: Trie = Trie::new(&["über", "übung", "uber"]);
+        assert_eq!(trie.keys_with_prefix("üb"), vec!["über", "übung"]);
+        assert_eq!(trie.keys_with_prefix("u"), vec!["uber"]);
+        assert_eq!(trie.lookup("ü"), None);
+    }
+
+    #[test]
+    fn empty_trie() {
+        let trie: Trie = Trie::new::<&str>(&[]);
+        assert!(trie.is_empty());
+        assert_eq!(trie.lookup(""), None);
+        assert_eq!(trie.lookup("a"), None);
+        assert_eq!(trie.key(0), None);
+        assert!(trie.keys_with_prefix("").is_empty());
+    }
+}
@@ -0,0 +1,264 @@
+// Implement a wavelet matrix over an integer alphabet (e.g. token ids) using one select-supported bit-vector per bit level
+// methods: access: return value at position i
+            // rank: number of occurrences of a value before position i (exclusive)
+            // select: position of the kth occurrence of a value
+            // range_freq: number of values in [lo, hi) within positions [l, r)
+            // top_k: k most frequent values within positions [l, r)
+            // next_value: smallest value >= x within positions [l, r)
+use std::collections::BinaryHeap;
+use std::cmp::Reverse;
+
+use bitvec::prelude::*;
+use crate::{FromBits, SpaceUsage, BitAccess, Rank};
+use serde::{Serialize,Deserialize};
+
+use crate::select::SelectSupport;
+
+#[derive(Serialize,Deserialize, Debug)]
+pub struct WaveletMatrix {
+    levels: Vec<SelectSupport>, // one bit-vector per bit of the values, most significant bit first
+    zeros: Vec<usize>, // number of 0s in each level (where the 1s start after the stable partition)
+    len: usize
+}
+
+impl WaveletMatrix {
+    pub fn new(values: &[usize]) -> WaveletMatrix {
+        // number of levels is the bit width of the largest value (at least 1 so a matrix of 0s still has a level)
+        let max_value: usize = values.iter().cloned().max().unwrap_or(0);
+        let num_levels: usize = ((usize::BITS - max_value.leading_zeros()) as usize).max(1);
+        let mut levels: Vec<SelectSupport> = Vec::with_capacity(num_levels);
+        let mut zeros: Vec<usize> = Vec::with_capacity(num_levels);
+        let mut current: Vec<usize> = values.to_vec();
+        for level in 0..num_levels {
+            let shift: usize = num_levels-1-level;
+            let bit_v: BitVec = current.iter().map(|v| (v >> shift) & 1 == 1).collect();
+            // stable partition: values with a 0 at this level go first, then those with a 1
+            let (mut next, ones): (Vec<usize>, Vec<usize>) = current.iter().partition(|v| (*v >> shift) & 1 == 0);
+            zeros.push(next.len());
+            next.extend(ones);
+            levels.push(FromBits::from_bits(bit_v));
+            current = next;
+        }
+        WaveletMatrix {levels, zeros, len: values.len()}
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn num_levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    fn bit_of(&self, value: usize, level: usize) -> bool {
+        // bit of value looked at by this level (levels go from most to least significant bit)
+        (value >> (self.levels.len()-1-level)) & 1 == 1
+    }
+
+    fn child(&self, level: usize, i: usize, bit: bool) -> usize {
+        // map position i of this level to its position in the next level
+        let bits: &SelectSupport = &self.levels[level];
+        if bit {
+            self.zeros[level] + bits.rank1(i)
+        } else {
+            bits.rank0(i)
+        }
+    }
+
+    pub fn access(&self, i: usize) -> usize {
+        // return value stored at position i
+        if i >= self.len {
+            panic!("invalid position entry");
+        }
+        let mut value: usize = 0;
+        let mut pos: usize = i;
+        for level in 0..self.levels.len() {
+            let bit: bool = self.levels[level].get(pos);
+            value = (value << 1) | bit as usize;
+            pos = self.child(level, pos, bit);
+        }
+        value
+    }
+
+    pub fn rank(&self, value: usize, i: usize) -> usize {
+        // number of occurrences of value in positions [0, i)
+        // (checked: a matrix of values >= 2^63 has 64 levels)
+        if value.checked_shr(self.levels.len() as u32).unwrap_or(0) != 0 {
+            return 0;
+        }
+        let mut start: usize = 0;
+        let mut end: usize = i.min(self.len);
+        for level in 0..self.levels.len() {
+            let bit: bool = self.bit_of(value, level);
+            start = self.child(level, start, bit);
+            end = self.child(level, end, bit);
+        }
+        end - start
+    }
+
+    pub fn select(&self, value: usize, k: usize) -> Option<usize> {
+        // position of the kth (starting at 1) occurrence of value, so rank(value, select(value, k)+1) = k
+        if k == 0 || self.rank(value, self.len) < k {
+            return None;
+        }
+        // walk down to where value's run starts in the last level
+        let mut start: usize = 0;
+        for level in 0..self.levels.len() {
+            start = self.child(level, start, self.bit_of(value, level));
+        }
+        // then walk back up, turning positions into the kth 0 or 1 of the level above
+        let mut pos: usize = start+k-1;
+        for level in (0..self.levels.len()).rev() {
+            if self.bit_of(value, level) {
+                pos = self.levels[level].select1(pos-self.zeros[level]+1)-1;
+            } else {
+                pos = self.levels[level].select0(pos+1)-1;
+            }
+        }
+        Some(pos)
+    }
+
+    fn count_less(&self, l: usize, r: usize, x: usize) -> usize {
+        // number of values < x in positions [l, r)
+        if x.checked_shr(self.levels.len() as u32).unwrap_or(0) != 0 {
+            return r-l;
+        }
+        let mut count: usize = 0;
+        let mut start: usize = l;
+        let mut end: usize = r;
+        for level in 0..self.levels.len() {
+            let bit: bool = self.bit_of(x, level);
+            if bit {
+                // everything going to the 0 side at this level is smaller than x
+                let bits: &SelectSupport = &self.levels[level];
+                count += bits.rank0(end) - bits.rank0(start);
+            }
+            start = self.child(level, start, bit);
+            end = self.child(level, end, bit);
+        }
+        count
+    }
+
+    pub fn range_freq(&self, l: usize, r: usize, lo: usize, hi: usize) -> usize {
+        // number of positions in [l, r) whose value is in [lo, hi)
+        let r: usize = r.min(self.len);
+        if l >= r || lo >= hi {
+            return 0;
+        }
+        self.count_less(l, r, hi) - self.count_less(l, r, lo)
+    }
+
+    pub fn top_k(&self, l: usize, r: usize, k: usize) -> Vec<(usize, usize)> {
+        // k most frequent values in positions [l, r) as (value, frequency), most frequent first
+        // expand the widest position range first so finished leaves come out in frequency order
+        let mut result: Vec<(usize, usize)> = Vec::new();
+        let r: usize = r.min(self.len);
+        if l >= r {
+            return result;
+        }
+        // entries are (width, value prefix with smallest first on ties, level, start)
+        let mut heap: BinaryHeap<(usize, Reverse<usize>, usize, usize)> = BinaryHeap::new();
+        heap.push((r-l, Reverse(0), 0, l));
+        while let Some((width, Reverse(value), level, start)) = heap.pop() {
+            if result.len() == k {
+                break;
+            }
+            if level == self.levels.len() {
+                result.push((value, width));
+                continue;
+            }
+            let end: usize = start+width;
+            let shift: usize = self.levels.len()-1-level;
+            for bit in [false, true] {
+                let child_start: usize = self.child(level, start, bit);
+                let child_end: usize = self.child(level, end, bit);
+                if child_end > child_start {
+                    let child_value: usize = value | ((bit as usize) << shift);
+                    heap.push((child_end-child_start, Reverse(child_value), level+1, child_start));
+                }
+            }
+        }
+        result
+    }
+
+    pub fn next_value(&self, l: usize, r: usize, x: usize) -> Option<usize> {
+        // smallest value >= x that appears in positions [l, r)
+        let r: usize = r.min(self.len);
+        if l >= r {
+            return None;
+        }
+        self.next_value_from(0, l, r, x, 0)
+    }
+
+    fn next_value_from(&self, level: usize, start: usize, end: usize, x: usize, value: usize) -> Option<usize> {
+        // depth first search preferring the 0 side, skipping subtrees whose values are all < x
+        if start >= end {
+            return None;
+        }
+        // values below this node are value..value+2^(levels-level)
+        let span_bits: usize = self.levels.len()-level;
+        if span_bits < usize::BITS as usize && value.saturating_add(1 << span_bits) <= x {
+            return None;
+        }
+        if level == self.levels.len() {
+            return Some(value);
+        }
+        let shift: usize = span_bits-1;
+        for bit in [false, true] {
+            let child_value: usize = value | ((bit as usize) << shift);
+            let found: Option<usize> = self.next_value_from(level+1, self.child(level, start, bit), self.child(level, end, bit), x, child_value);
+            if found.is_some() {
+                return found;
+            }
+        }
+        None
+    }
+
+    pub fn size(&self) -> usize {
+        // size of wavelet matrix in bits (level bit-vectors, their rank structures and zero counts)
+        self.levels.iter().map(|level| level.len() + level.overhead()).sum::<usize>()
+            + self.zeros.len()*usize::BITS as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queries_match_naive() {
+        let values: Vec<usize> = vec![5, 1, 0, 7, 5, 3, 1, 5, 2, 6, 0, 5];
+        let matrix: WaveletMatrix = WaveletMatrix::new(&values);
+        for (i, &value) in values.iter().enumerate() {
+            assert_eq!(matrix.access(i), value);
+        }
+        for value in 0..10 {
+            for i in 0..=values.len() {
+                assert_eq!(matrix.rank(value, i), values[..i].iter().filter(|&&v| v == value).count());
+            }
+        }
+        for (l, r) in [(0, 12), (2, 9), (4, 4)] {
+            for (lo, hi) in [(0, 8), (1, 5), (5, 6), (3, 100)] {
+                let expected: usize = values[l..r].iter().filter(|&&v| lo <= v && v < hi).count();
+                assert_eq!(matrix.range_freq(l, r, lo, hi), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn full_width_values() {
+        // 64 levels, shifting a value by the level count would overflow
+        let values: Vec<usize> = vec![usize::MAX, 3, 0, 1 << 63, 3];
+        let matrix: WaveletMatrix = WaveletMatrix::new(&values);
+        assert_eq!(matrix.num_levels(), 64);
+        assert_eq!(matrix.rank(3, 5), 2);
+        assert_eq!(matrix.rank(usize::MAX, 5), 1);
+        assert_eq!(matrix.select(1 << 63, 1), Some(3));
+        assert_eq!(matrix.range_freq(0, 5, 0, usize::MAX), 4);
+        assert_eq!(matrix.range_freq(0, 5, 1 << 63, usize::MAX), 1);
+    }
+}